@@ -1,60 +1,141 @@
+use crate::error::{Context, FaeError};
+use crate::resources::{FaeResources, MemoryCategory, Release};
 use ash::vk;
+use std::rc::Rc;
 
+// destroyed through the resource context's deletion queue once it is dropped. The name shows
+// up in validation messages, debugging tools and the leak report.
 pub struct Buffer {
     pub buffer: vk::Buffer,
-    pub allocation: vk_mem::Allocation,
+    allocation: vk_mem::Allocation,
+    // host visible buffers stay mapped for their whole life, null for GpuOnly
+    mapped: *mut u8,
     size_in_bytes: u64,
     buffer_usage: vk::BufferUsageFlags,
     memory_usage: vk_mem::MemoryUsage,
+    // debug name and report category, kept for the replacement buffer in fill
+    name: String,
+    category: MemoryCategory,
+    resources: Rc<FaeResources>,
+    id: u64,
 }
 impl Buffer {
+    // the memory report category is taken from the usage, see MemoryCategory::of_buffer
     pub fn new(
-        allocator: &vk_mem::Allocator,
+        resources: &Rc<FaeResources>,
+        name: &str,
         size_in_bytes: u64,
         buffer_usage: vk::BufferUsageFlags,
         memory_usage: vk_mem::MemoryUsage,
-    ) -> Result<Buffer, vk_mem::error::Error> {
+    ) -> Result<Buffer, FaeError> {
+        let category = MemoryCategory::of_buffer(buffer_usage, memory_usage);
+        Buffer::with_category(
+            resources,
+            name,
+            category,
+            size_in_bytes,
+            buffer_usage,
+            memory_usage,
+        )
+    }
+
+    pub fn with_category(
+        resources: &Rc<FaeResources>,
+        name: &str,
+        category: MemoryCategory,
+        size_in_bytes: u64,
+        buffer_usage: vk::BufferUsageFlags,
+        memory_usage: vk_mem::MemoryUsage,
+    ) -> Result<Buffer, FaeError> {
+        let host_visible = memory_usage != vk_mem::MemoryUsage::GpuOnly;
         let allocation_create_info = vk_mem::AllocationCreateInfo {
             usage: memory_usage,
+            flags: if host_visible {
+                vk_mem::AllocationCreateFlags::MAPPED
+            } else {
+                vk_mem::AllocationCreateFlags::NONE
+            },
             ..Default::default()
         };
 
         // create buffer
-        let (buffer, allocation, _allocation_info) = allocator.create_buffer(
-            &ash::vk::BufferCreateInfo::builder()
-                .size(size_in_bytes)
-                .usage(buffer_usage)
-                .build(),
-            &allocation_create_info,
-        )?;
+        let (buffer, allocation, allocation_info) = resources
+            .allocator
+            .create_buffer(
+                &ash::vk::BufferCreateInfo::builder()
+                    .size(size_in_bytes)
+                    .usage(buffer_usage)
+                    .build(),
+                &allocation_create_info,
+            )
+            .context("allocating a buffer")?;
+        resources.set_object_name(buffer, name);
+        let allocated = allocation_info.get_size() as u64;
+        let id = resources.track(category, allocated, || {
+            format!(
+                "{}, {} bytes ({:?}, {:?})",
+                name, size_in_bytes, buffer_usage, memory_usage
+            )
+        });
         Ok(Buffer {
             buffer,
             allocation,
+            mapped: allocation_info.get_mapped_data(),
             size_in_bytes,
             buffer_usage,
             memory_usage,
+            name: name.to_string(),
+            category,
+            resources: resources.clone(),
+            id,
         })
     }
 
-    pub fn fill<T: Sized>(
-        &mut self,
-        allocator: &vk_mem::Allocator,
-        data: &[T],
-    ) -> Result<(), vk_mem::error::Error> {
+    // writes the data, replacing the buffer with a larger one if it does not fit. The old buffer
+    // may still be in use by frames in flight, dropping it only queues it for destruction.
+    pub fn fill<T: Sized>(&mut self, data: &[T]) -> Result<(), FaeError> {
         let bytes_to_write = (data.len() * std::mem::size_of::<T>()) as u64;
         if bytes_to_write > self.size_in_bytes {
-            allocator.destroy_buffer(self.buffer, &self.allocation)?;
-            let new_buffer = Buffer::new(
-                allocator,
+            *self = Buffer::with_category(
+                &self.resources,
+                &self.name,
+                self.category,
                 bytes_to_write,
                 self.buffer_usage,
                 self.memory_usage,
             )?;
-            *self = new_buffer;
         }
-        let data_ptr = allocator.map_memory(&self.allocation)? as *mut T;
-        unsafe { data_ptr.copy_from_nonoverlapping(data.as_ptr(), data.len()) };
-        allocator.unmap_memory(&self.allocation)?;
+        self.write_at(0, data);
         Ok(())
     }
+
+    // copies the data into the mapped memory, panics if it does not fit or the buffer is GpuOnly
+    pub fn write_at<T: Sized>(&mut self, offset: u64, data: &[T]) {
+        let bytes_to_write = (data.len() * std::mem::size_of::<T>()) as u64;
+        assert!(!self.mapped.is_null(), "buffer is not host visible");
+        assert!(
+            offset + bytes_to_write <= self.size_in_bytes,
+            "write past the end of the buffer"
+        );
+        unsafe {
+            let data_ptr = self.mapped.add(offset as usize) as *mut T;
+            data_ptr.copy_from_nonoverlapping(data.as_ptr(), data.len());
+        }
+    }
+
+    pub fn size_in_bytes(&self) -> u64 {
+        self.size_in_bytes
+    }
+
+    pub fn read_bytes(&self) -> Vec<u8> {
+        assert!(!self.mapped.is_null(), "buffer is not host visible");
+        unsafe { std::slice::from_raw_parts(self.mapped, self.size_in_bytes as usize) }.to_vec()
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        self.resources
+            .release(self.id, Release::Buffer(self.buffer, self.allocation));
+    }
 }
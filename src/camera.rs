@@ -1,4 +1,5 @@
 use crate::Buffer;
+use ash::vk;
 use nalgebra as na;
 pub struct CameraBuilder {
     position: na::Vector3<f32>,
@@ -13,9 +14,10 @@ pub struct CameraBuilder {
 impl CameraBuilder {
     pub fn build(self) -> Camera {
         if self.far < self.near {
-            println!(
+            log::debug!(
                 "far plane (at {}) closer than near plane (at {}) - is that right?",
-                self.far, self.near
+                self.far,
+                self.near
             );
         }
         let mut cam = Camera {
@@ -40,37 +42,41 @@ impl CameraBuilder {
         cam.update_view_matrix();
         cam
     }
-    fn position(mut self, pos: na::Vector3<f32>) -> CameraBuilder {
+    pub fn position(mut self, pos: na::Vector3<f32>) -> CameraBuilder {
         self.position = pos;
         self
     }
-    fn fovy(mut self, fovy: f32) -> CameraBuilder {
+    pub fn fovy(mut self, fovy: f32) -> CameraBuilder {
         self.fovy = fovy.max(0.01).min(std::f32::consts::PI - 0.01);
         self
     }
-    fn aspect(mut self, aspect: f32) -> CameraBuilder {
+    pub fn aspect(mut self, aspect: f32) -> CameraBuilder {
         self.aspect = aspect;
         self
     }
-    fn near(mut self, near: f32) -> CameraBuilder {
+    // aspect ratio of the image the camera renders into
+    pub fn extent(self, extent: vk::Extent2D) -> CameraBuilder {
+        self.aspect(aspect_of(extent))
+    }
+    pub fn near(mut self, near: f32) -> CameraBuilder {
         if near <= 0.0 {
-            println!("setting near plane to negative value: {} - you sure?", near);
+            log::debug!("setting near plane to negative value: {} - you sure?", near);
         }
         self.near = near;
         self
     }
-    fn far(mut self, far: f32) -> CameraBuilder {
+    pub fn far(mut self, far: f32) -> CameraBuilder {
         if far <= 0.0 {
-            println!("setting far plane to negative value: {} - you sure?", far);
+            log::debug!("setting far plane to negative value: {} - you sure?", far);
         }
         self.far = far;
         self
     }
-    fn view_direction(mut self, direction: na::Vector3<f32>) -> CameraBuilder {
+    pub fn view_direction(mut self, direction: na::Vector3<f32>) -> CameraBuilder {
         self.view_direction = na::Unit::new_normalize(direction);
         self
     }
-    fn down_direction(mut self, direction: na::Vector3<f32>) -> CameraBuilder {
+    pub fn down_direction(mut self, direction: na::Vector3<f32>) -> CameraBuilder {
         self.down_direction = na::Unit::new_normalize(direction);
         self
     }
@@ -87,6 +93,7 @@ pub struct Camera {
     projection_matrix: na::Matrix4<f32>,
 }
 impl Camera {
+    // the aspect only matters for uniform_data, Fae::draw_views uses the size of each view
     pub fn builder() -> CameraBuilder {
         CameraBuilder {
             position: na::Vector3::new(0.0, -3.0, -3.0),
@@ -98,14 +105,37 @@ impl Camera {
             far: 100.0,
         }
     }
-    pub fn update_buffer(&self, allocator: &vk_mem::Allocator, buffer: &mut Buffer) {
-        let data: [[[f32; 4]; 4]; 2] = [self.view_matrix.into(), self.projection_matrix.into()];
-        buffer.fill(allocator, &data).unwrap();
+    // view and projection matrix as laid out in the shader's uniform block
+    pub fn uniform_data(&self) -> [[[f32; 4]; 4]; 2] {
+        [self.view_matrix.into(), self.projection_matrix.into()]
+    }
+    // like uniform_data, but with the aspect of the given render target instead of the camera's
+    pub fn uniform_data_for(&self, extent: vk::Extent2D) -> [[[f32; 4]; 4]; 2] {
+        if extent.width == 0 || extent.height == 0 {
+            return self.uniform_data();
+        }
+        let projection_matrix = self.projection_matrix_for(aspect_of(extent));
+        [self.view_matrix.into(), projection_matrix.into()]
+    }
+    pub fn update_buffer(&self, buffer: &mut Buffer) {
+        buffer.write_at(0, &self.uniform_data());
+    }
+    // for uniform_data, call whenever the render target changes size so the image is not
+    // stretched. Fae::draw_views does not need it.
+    pub fn set_extent(&mut self, extent: vk::Extent2D) {
+        if extent.width == 0 || extent.height == 0 {
+            return;
+        }
+        self.aspect = aspect_of(extent);
+        self.update_projection_matrix();
     }
     fn update_projection_matrix(&mut self) {
+        self.projection_matrix = self.projection_matrix_for(self.aspect);
+    }
+    fn projection_matrix_for(&self, aspect: f32) -> na::Matrix4<f32> {
         let d = 1.0 / (0.5 * self.fovy).tan();
-        self.projection_matrix = na::Matrix4::new(
-            d / self.aspect,
+        na::Matrix4::new(
+            d / aspect,
             0.0,
             0.0,
             0.0,
@@ -121,7 +151,7 @@ impl Camera {
             0.0,
             1.0,
             0.0,
-        );
+        )
     }
     fn update_view_matrix(&mut self) {
         let right = na::Unit::new_normalize(self.down_direction.cross(&self.view_direction));
@@ -179,3 +209,140 @@ impl Camera {
         self.turn_up(-angle);
     }
 }
+
+fn aspect_of(extent: vk::Extent2D) -> f32 {
+    extent.width as f32 / extent.height.max(1) as f32
+}
+
+// part of the frame a view renders into, in fractions of the frame's width and height so it
+// keeps its place when the window is resized. (0, 0) is the top left corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ViewRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl ViewRect {
+    pub const FULL: ViewRect = ViewRect {
+        x: 0.0,
+        y: 0.0,
+        width: 1.0,
+        height: 1.0,
+    };
+
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> ViewRect {
+        ViewRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    // the pixels covered in a frame of the given size, clamped to the frame
+    pub fn scissor(&self, frame: vk::Extent2D) -> vk::Rect2D {
+        let to_pixels =
+            |fraction: f32, size: u32| (fraction.max(0.0).min(1.0) * size as f32).round() as u32;
+        let left = to_pixels(self.x, frame.width);
+        let top = to_pixels(self.y, frame.height);
+        let right = to_pixels(self.x + self.width, frame.width).max(left);
+        let bottom = to_pixels(self.y + self.height, frame.height).max(top);
+        vk::Rect2D {
+            offset: vk::Offset2D {
+                x: left as i32,
+                y: top as i32,
+            },
+            extent: vk::Extent2D {
+                width: right - left,
+                height: bottom - top,
+            },
+        }
+    }
+
+    // size in pixels, draw_views takes the aspect of each view's camera from it
+    pub fn extent(&self, frame: vk::Extent2D) -> vk::Extent2D {
+        self.scissor(frame).extent
+    }
+
+    pub fn viewport(&self, frame: vk::Extent2D) -> vk::Viewport {
+        let scissor = self.scissor(frame);
+        vk::Viewport {
+            x: scissor.offset.x as f32,
+            y: scissor.offset.y as f32,
+            width: scissor.extent.width as f32,
+            height: scissor.extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        }
+    }
+}
+
+// a camera and where in the frame it renders, see Fae::draw_views
+#[derive(Clone, Copy)]
+pub struct View<'a> {
+    pub camera: &'a Camera,
+    pub rect: ViewRect,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(width: u32, height: u32) -> vk::Extent2D {
+        vk::Extent2D { width, height }
+    }
+
+    #[test]
+    fn split_screen_covers_the_frame_once() {
+        // an odd width can not be split evenly, the halves still meet without a gap
+        let frame = frame(801, 600);
+        let left = ViewRect::new(0.0, 0.0, 0.5, 1.0).scissor(frame);
+        let right = ViewRect::new(0.5, 0.0, 0.5, 1.0).scissor(frame);
+        assert_eq!(left.offset.x, 0);
+        assert_eq!(
+            left.offset.x as u32 + left.extent.width,
+            right.offset.x as u32
+        );
+        assert_eq!(right.offset.x as u32 + right.extent.width, 801);
+        assert_eq!((left.extent.height, right.extent.height), (600, 600));
+    }
+
+    #[test]
+    fn rects_are_clamped_to_the_frame() {
+        let frame = frame(800, 600);
+        assert_eq!(
+            ViewRect::FULL.scissor(frame),
+            vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: frame,
+            }
+        );
+        let outside = ViewRect::new(0.75, -0.5, 0.5, 1.0).scissor(frame);
+        assert_eq!(outside.offset, vk::Offset2D { x: 600, y: 0 });
+        assert_eq!(
+            outside.extent,
+            vk::Extent2D {
+                width: 200,
+                height: 300
+            }
+        );
+        // nothing left of a rect outside the frame, but no underflow either
+        assert_eq!(ViewRect::new(1.5, 0.0, 0.5, 1.0).extent(frame).width, 0);
+    }
+
+    #[test]
+    fn viewport_matches_scissor() {
+        let frame = frame(1280, 720);
+        let rect = ViewRect::new(0.7, 0.05, 0.25, 0.25);
+        let scissor = rect.scissor(frame);
+        let viewport = rect.viewport(frame);
+        assert_eq!(viewport.x, scissor.offset.x as f32);
+        assert_eq!(viewport.y, scissor.offset.y as f32);
+        assert_eq!(viewport.width, scissor.extent.width as f32);
+        assert_eq!(viewport.height, scissor.extent.height as f32);
+        assert_eq!(rect.extent(frame), scissor.extent);
+        assert_eq!((viewport.min_depth, viewport.max_depth), (0.0, 1.0));
+    }
+}
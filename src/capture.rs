@@ -0,0 +1,159 @@
+use crate::error::FaeError;
+use ash::{version::DeviceV1_0, vk};
+
+// pixels read back from the color attachment, always stored as tightly packed RGBA8
+pub struct CapturedFrame {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+impl CapturedFrame {
+    pub fn from_raw(
+        width: u32,
+        height: u32,
+        format: vk::Format,
+        mut data: Vec<u8>,
+    ) -> CapturedFrame {
+        // swapchain images are usually BGRA, swap red and blue so the png looks right
+        if is_bgra(format) {
+            for pixel in data.chunks_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        CapturedFrame {
+            width,
+            height,
+            rgba: data,
+        }
+    }
+
+    pub fn load_png<P: AsRef<std::path::Path>>(path: P) -> Result<CapturedFrame, FaeError> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path).map_err(FaeError::asset(path))?;
+        let decoder = png::Decoder::new(file);
+        let (info, mut reader) = decoder.read_info().map_err(FaeError::asset(path))?;
+        if info.color_type != png::ColorType::RGBA || info.bit_depth != png::BitDepth::Eight {
+            return Err(FaeError::Asset {
+                path: path.to_path_buf(),
+                message: format!(
+                    "expected 8 bit RGBA png, got {:?} {:?}",
+                    info.color_type, info.bit_depth
+                ),
+            });
+        }
+        let mut rgba = vec![0; info.buffer_size()];
+        reader
+            .next_frame(&mut rgba)
+            .map_err(FaeError::asset(path))?;
+        Ok(CapturedFrame {
+            width: info.width,
+            height: info.height,
+            rgba,
+        })
+    }
+
+    pub fn save_png<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), FaeError> {
+        let path = path.as_ref();
+        let file = std::fs::File::create(path).map_err(FaeError::asset(path))?;
+        let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(FaeError::asset(path))?;
+        writer
+            .write_image_data(&self.rgba)
+            .map_err(FaeError::asset(path))
+    }
+}
+
+// formats with 8 bit per channel in RGBA or BGRA order, the only ones from_raw can read
+pub fn is_capturable(format: vk::Format) -> bool {
+    format == vk::Format::R8G8B8A8_UNORM || format == vk::Format::R8G8B8A8_SRGB || is_bgra(format)
+}
+
+fn is_bgra(format: vk::Format) -> bool {
+    format == vk::Format::B8G8R8A8_UNORM || format == vk::Format::B8G8R8A8_SRGB
+}
+
+// records a copy of the whole color image into the buffer, the image is moved to
+// TRANSFER_SRC_OPTIMAL for the copy and then put back into its original layout
+pub fn record_copy_image_to_buffer(
+    logical_device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    image: vk::Image,
+    layout: vk::ImageLayout,
+    extent: vk::Extent2D,
+    buffer: vk::Buffer,
+) {
+    let subresource_range = vk::ImageSubresourceRange {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        base_mip_level: 0,
+        level_count: 1,
+        base_array_layer: 0,
+        layer_count: 1,
+    };
+    let to_transfer = vk::ImageMemoryBarrier::builder()
+        .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+        .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+        .old_layout(layout)
+        .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image)
+        .subresource_range(subresource_range)
+        .build();
+    let from_transfer = vk::ImageMemoryBarrier::builder()
+        .src_access_mask(vk::AccessFlags::TRANSFER_READ)
+        .dst_access_mask(vk::AccessFlags::MEMORY_READ)
+        .old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+        .new_layout(layout)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image)
+        .subresource_range(subresource_range)
+        .build();
+    let region = vk::BufferImageCopy {
+        buffer_offset: 0,
+        buffer_row_length: 0,
+        buffer_image_height: 0,
+        image_subresource: vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            mip_level: 0,
+            base_array_layer: 0,
+            layer_count: 1,
+        },
+        image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+        image_extent: vk::Extent3D {
+            width: extent.width,
+            height: extent.height,
+            depth: 1,
+        },
+    };
+    unsafe {
+        logical_device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[to_transfer],
+        );
+        logical_device.cmd_copy_image_to_buffer(
+            command_buffer,
+            image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            buffer,
+            &[region],
+        );
+        logical_device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[from_transfer],
+        );
+    }
+}
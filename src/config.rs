@@ -0,0 +1,150 @@
+use crate::debug::DebugConfig;
+use ash::vk;
+
+// settings used when creating a Fae, start from FaeConfig::default()
+#[derive(Debug, Clone)]
+pub struct FaeConfig {
+    // how many frames the cpu may record while the gpu is still working on earlier ones
+    pub frames_in_flight: usize,
+    pub swapchain: SwapchainConfig,
+    // the FAE_DEVICE environment variable takes precedence over this
+    pub device: DeviceSelection,
+    // enable the khronos validation layer if it is installed, FAE_VALIDATION=1 does the same.
+    // Release builds never enable any layers.
+    pub validation: bool,
+    // only used when debug utils is available
+    pub debug: DebugConfig,
+    // time the render pass and every model draw with timestamp queries, see Fae::profiler
+    pub profiling: bool,
+    // pipeline statistics (if supported) and occlusion queries per model, see
+    // Fae::frame_statistics
+    pub statistics: bool,
+}
+
+impl FaeConfig {
+    // layers to request from the instance and device
+    pub fn layer_names(&self) -> Vec<&'static str> {
+        let requested =
+            self.validation || std::env::var_os("FAE_VALIDATION").map_or(false, |v| v != "0");
+        if cfg!(debug_assertions) && requested {
+            vec!["VK_LAYER_KHRONOS_validation"]
+        } else {
+            vec![]
+        }
+    }
+}
+
+impl Default for FaeConfig {
+    fn default() -> FaeConfig {
+        FaeConfig {
+            frames_in_flight: 2,
+            swapchain: SwapchainConfig::default(),
+            device: DeviceSelection::Auto,
+            validation: false,
+            debug: DebugConfig::default(),
+            profiling: false,
+            statistics: false,
+        }
+    }
+}
+
+// which physical device to render with, see list_physical_devices for the candidates
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviceSelection {
+    // the suitable device with the highest score
+    Auto,
+    // position in the list of physical devices
+    Index(usize),
+    // first suitable device whose name contains this, ignoring case
+    Name(String),
+}
+
+impl DeviceSelection {
+    // a number selects by index, anything else by name
+    pub fn parse(value: &str) -> DeviceSelection {
+        match value.trim().parse() {
+            Ok(index) => DeviceSelection::Index(index),
+            Err(_) => DeviceSelection::Name(value.trim().to_string()),
+        }
+    }
+}
+
+// what the swapchain should look like, each list is tried in order and the first entry the
+// surface supports wins. The choice is available from Fae::present_mode and
+// Fae::surface_format afterwards.
+#[derive(Debug, Clone)]
+pub struct SwapchainConfig {
+    // FIFO is always supported and used when none of these are
+    pub present_modes: Vec<vk::PresentModeKHR>,
+    // falls back to a listed format with another color space, then to the surface's first format
+    pub surface_formats: Vec<vk::SurfaceFormatKHR>,
+}
+
+impl SwapchainConfig {
+    // lowest latency without tearing where possible
+    pub fn low_latency() -> SwapchainConfig {
+        SwapchainConfig {
+            present_modes: vec![
+                vk::PresentModeKHR::MAILBOX,
+                vk::PresentModeKHR::IMMEDIATE,
+                vk::PresentModeKHR::FIFO_RELAXED,
+            ],
+            ..SwapchainConfig::default()
+        }
+    }
+
+    // let the presentation engine do the gamma encoding
+    pub fn srgb() -> SwapchainConfig {
+        SwapchainConfig {
+            surface_formats: vec![
+                vk::SurfaceFormatKHR {
+                    format: vk::Format::B8G8R8A8_SRGB,
+                    color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+                },
+                vk::SurfaceFormatKHR {
+                    format: vk::Format::R8G8B8A8_SRGB,
+                    color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+                },
+            ],
+            ..SwapchainConfig::default()
+        }
+    }
+}
+
+impl Default for SwapchainConfig {
+    fn default() -> SwapchainConfig {
+        SwapchainConfig {
+            present_modes: vec![vk::PresentModeKHR::FIFO],
+            surface_formats: vec![
+                vk::SurfaceFormatKHR {
+                    format: vk::Format::B8G8R8A8_UNORM,
+                    color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+                },
+                vk::SurfaceFormatKHR {
+                    format: vk::Format::R8G8B8A8_UNORM,
+                    color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+                },
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_device_selection() {
+        assert_eq!(DeviceSelection::parse("1"), DeviceSelection::Index(1));
+        assert_eq!(DeviceSelection::parse(" 0 "), DeviceSelection::Index(0));
+        assert_eq!(
+            DeviceSelection::parse(" llvmpipe "),
+            DeviceSelection::Name("llvmpipe".to_string())
+        );
+        // negative numbers are not indices
+        assert_eq!(
+            DeviceSelection::parse("-1"),
+            DeviceSelection::Name("-1".to_string())
+        );
+    }
+}
@@ -1,30 +1,119 @@
 use ash::vk;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 pub struct FaeDebug {
     loader: ash::extensions::ext::DebugUtils,
     messenger: vk::DebugUtilsMessengerEXT,
+    // handed to the callback as user data, boxed so its address stays fixed
+    state: Box<DebugState>,
+    panic_on_error: bool,
+}
+
+// which messages reach the log and what happens on validation errors
+#[derive(Debug, Clone)]
+pub struct DebugConfig {
+    pub severities: vk::DebugUtilsMessageSeverityFlagsEXT,
+    pub types: vk::DebugUtilsMessageTypeFlagsEXT,
+    // panic from FaeDebug::check_errors, called before every frame, once an error was reported
+    pub panic_on_error: bool,
+}
+
+impl Default for DebugConfig {
+    fn default() -> DebugConfig {
+        DebugConfig {
+            severities: vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
+                | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+            types: vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE
+                | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
+            panic_on_error: false,
+        }
+    }
+}
+
+// errors are delivered whatever the config says so they can be counted, the callback only logs
+// what matches severities and types
+struct DebugState {
+    errors: AtomicUsize,
+    severities: vk::DebugUtilsMessageSeverityFlagsEXT,
+    types: vk::DebugUtilsMessageTypeFlagsEXT,
 }
 
 impl FaeDebug {
-    pub fn init(entry: &ash::Entry, instance: &ash::Instance) -> Result<FaeDebug, vk::Result> {
-        // setup debug messeges
+    pub fn init(
+        entry: &ash::Entry,
+        instance: &ash::Instance,
+        config: &DebugConfig,
+    ) -> Result<FaeDebug, vk::Result> {
+        let state = Box::new(DebugState {
+            errors: AtomicUsize::new(0),
+            severities: config.severities,
+            types: config.types,
+        });
         let debug_create_info = vk::DebugUtilsMessengerCreateInfoEXT::builder()
-            .message_severity(
-                vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
-                    | vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE
-                    | vk::DebugUtilsMessageSeverityFlagsEXT::INFO
-                    | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
-            )
+            .message_severity(config.severities | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR)
             .message_type(
-                vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                config.types
+                    | vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
                     | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE
                     | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
             )
-            .pfn_user_callback(Some(vulkan_debug_utils_callback));
+            .pfn_user_callback(Some(vulkan_debug_utils_callback))
+            .user_data(&*state as *const DebugState as *mut std::ffi::c_void);
 
         let loader = ash::extensions::ext::DebugUtils::new(entry, instance);
         let messenger = unsafe { loader.create_debug_utils_messenger(&debug_create_info, None)? };
 
-        Ok(FaeDebug { loader, messenger })
+        Ok(FaeDebug {
+            loader,
+            messenger,
+            state,
+            panic_on_error: config.panic_on_error,
+        })
+    }
+
+    // number of validation errors reported since init
+    pub fn error_count(&self) -> usize {
+        self.state.errors.load(Ordering::SeqCst)
+    }
+
+    // names show up in validation messages and tools like renderdoc
+    pub fn set_object_name<H: vk::Handle>(
+        &self,
+        logical_device: &ash::Device,
+        handle: H,
+        name: &str,
+    ) {
+        set_object_name(&self.loader, logical_device, handle, name);
+    }
+
+    // FaeResources keeps a copy to name buffers and images as they are created
+    pub(crate) fn loader(&self) -> &ash::extensions::ext::DebugUtils {
+        &self.loader
+    }
+
+    // starts a named region in the command buffer, has to be closed with end_label
+    pub fn begin_label(&self, command_buffer: vk::CommandBuffer, name: &str, color: [f32; 4]) {
+        let name = std::ffi::CString::new(name).unwrap_or_default();
+        let label = vk::DebugUtilsLabelEXT::builder()
+            .label_name(&name)
+            .color(color);
+        unsafe {
+            self.loader
+                .cmd_begin_debug_utils_label(command_buffer, &label)
+        };
+    }
+
+    pub fn end_label(&self, command_buffer: vk::CommandBuffer) {
+        unsafe { self.loader.cmd_end_debug_utils_label(command_buffer) };
+    }
+
+    // panicking inside the callback would unwind through the driver, so it happens here instead
+    pub fn check_errors(&self) {
+        let errors = self.error_count();
+        if self.panic_on_error && errors > 0 {
+            panic!("{} vulkan validation errors were reported", errors);
+        }
     }
 }
 
@@ -37,16 +126,52 @@ impl Drop for FaeDebug {
     }
 }
 
-// external function call to setup validation layer callbacks
+// failures are ignored because the names are only a debugging aid
+pub(crate) fn set_object_name<H: vk::Handle>(
+    loader: &ash::extensions::ext::DebugUtils,
+    logical_device: &ash::Device,
+    handle: H,
+    name: &str,
+) {
+    let name = match std::ffi::CString::new(name) {
+        Ok(name) => name,
+        Err(_) => return,
+    };
+    let name_info = vk::DebugUtilsObjectNameInfoEXT::builder()
+        .object_type(H::TYPE)
+        .object_handle(handle.as_raw())
+        .object_name(&name);
+    let _ = unsafe { loader.debug_utils_set_object_name(logical_device.handle(), &name_info) };
+}
+
+// external function call to setup validation layer callbacks, forwards messages to the log
+// crate with the target "vulkan". User data is null for messages during instance creation,
+// that messenger is created with the configured filter and everything it gets is logged.
 pub unsafe extern "system" fn vulkan_debug_utils_callback(
     message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
     message_type: vk::DebugUtilsMessageTypeFlagsEXT,
     p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
-    _p_user_data: *mut std::ffi::c_void,
+    p_user_data: *mut std::ffi::c_void,
 ) -> vk::Bool32 {
     let message = std::ffi::CStr::from_ptr((*p_callback_data).p_message);
-    let severity = format!("{:?}", message_severity).to_lowercase();
-    let ty = format!("{:?}", message_type).to_lowercase();
-    println!("[Debug][{}][{}] {:?}", severity, ty, message);
+    let level = match message_severity {
+        vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => log::Level::Error,
+        vk::DebugUtilsMessageSeverityFlagsEXT::WARNING => log::Level::Warn,
+        vk::DebugUtilsMessageSeverityFlagsEXT::INFO => log::Level::Info,
+        _ => log::Level::Trace,
+    };
+    let mut logged = true;
+    if !p_user_data.is_null() {
+        let state = &*(p_user_data as *const DebugState);
+        if message_severity == vk::DebugUtilsMessageSeverityFlagsEXT::ERROR {
+            state.errors.fetch_add(1, Ordering::SeqCst);
+        }
+        logged =
+            state.severities.contains(message_severity) && state.types.intersects(message_type);
+    }
+    if logged {
+        let ty = format!("{:?}", message_type).to_lowercase();
+        log::log!(target: "vulkan", level, "[{}] {}", ty, message.to_string_lossy());
+    }
     vk::FALSE
 }
@@ -0,0 +1,149 @@
+use crate::instance_device_queues::DeviceCandidate;
+use ash::vk;
+
+// everything that can go wrong in the renderer. The step says what was being done when it
+// failed, like "creating the swapchain", since most vulkan calls can fail the same way.
+#[derive(Debug)]
+pub enum FaeError {
+    // the vulkan library could not be loaded
+    Loading(ash::LoadingError),
+    Vulkan {
+        step: &'static str,
+        result: vk::Result,
+    },
+    Allocation {
+        step: &'static str,
+        error: vk_mem::error::Error,
+    },
+    // the window surface can not be created or used
+    Surface {
+        step: &'static str,
+        result: vk::Result,
+    },
+    // a shader module could not be built
+    Shader {
+        step: &'static str,
+        message: String,
+    },
+    // reading or writing a file like a captured frame failed
+    Asset {
+        path: std::path::PathBuf,
+        message: String,
+    },
+    // the frame can not be copied out, because of its format or missing TRANSFER_SRC usage
+    CaptureNotSupported(String),
+    // render_offscreen was called on a renderer that draws to a window
+    NotOffscreen,
+    // neither a swapchain nor offscreen images exist, for example after a failed device
+    // recovery
+    NoRenderTarget,
+    // no model instance has this handle
+    InvalidHandle(usize),
+    // a model asks for a pipeline that was never added
+    UnknownPipeline(String),
+    // no physical device meets the requirements, the candidates list the reasons
+    NoSuitableDevice(Vec<DeviceCandidate>),
+}
+
+impl FaeError {
+    // for map_err on the surface queries, which fail with plain vulkan results
+    pub(crate) fn surface(step: &'static str) -> impl FnOnce(vk::Result) -> FaeError {
+        move |result| FaeError::Surface { step, result }
+    }
+
+    pub(crate) fn asset<P: AsRef<std::path::Path>, E: std::fmt::Display>(
+        path: P,
+    ) -> impl FnOnce(E) -> FaeError {
+        let path = path.as_ref().to_path_buf();
+        move |error| FaeError::Asset {
+            path,
+            message: error.to_string(),
+        }
+    }
+
+    // the vulkan result behind the error, if there is one
+    pub fn vk_result(&self) -> Option<vk::Result> {
+        match self {
+            FaeError::Vulkan { result, .. } | FaeError::Surface { result, .. } => Some(*result),
+            _ => None,
+        }
+    }
+
+    // everything created from the device has to be rebuilt, see Fae::recover_device
+    pub fn is_device_lost(&self) -> bool {
+        self.vk_result() == Some(vk::Result::ERROR_DEVICE_LOST)
+    }
+}
+
+impl std::fmt::Display for FaeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FaeError::Loading(error) => write!(f, "could not load vulkan: {}", error),
+            FaeError::Vulkan { step, result } => write!(f, "{} failed: {}", step, result),
+            FaeError::Allocation { step, error } => write!(f, "{} failed: {}", step, error),
+            FaeError::Surface { step, result } => {
+                write!(f, "{} failed for the surface: {}", step, result)
+            }
+            FaeError::Shader { step, message } => write!(f, "{} failed: {}", step, message),
+            FaeError::Asset { path, message } => write!(f, "{}: {}", path.display(), message),
+            FaeError::CaptureNotSupported(reason) => {
+                write!(f, "the frame can not be captured: {}", reason)
+            }
+            FaeError::NotOffscreen => {
+                write!(
+                    f,
+                    "render_offscreen needs a renderer created with init_headless"
+                )
+            }
+            FaeError::NoRenderTarget => {
+                write!(
+                    f,
+                    "the renderer has neither a swapchain nor offscreen images"
+                )
+            }
+            FaeError::InvalidHandle(handle) => write!(f, "invalid handle {}", handle),
+            FaeError::UnknownPipeline(name) => write!(f, "there is no pipeline named {}", name),
+            FaeError::NoSuitableDevice(candidates) => {
+                write!(f, "no suitable physical device")?;
+                for candidate in candidates {
+                    write!(f, "\n  {}", candidate)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for FaeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FaeError::Loading(error) => Some(error),
+            FaeError::Vulkan { result, .. } | FaeError::Surface { result, .. } => Some(result),
+            FaeError::Allocation { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ash::LoadingError> for FaeError {
+    fn from(error: ash::LoadingError) -> FaeError {
+        FaeError::Loading(error)
+    }
+}
+
+// attaches the step to vulkan and allocation errors, `call().context("creating x")?`
+pub(crate) trait Context<T> {
+    fn context(self, step: &'static str) -> Result<T, FaeError>;
+}
+
+impl<T> Context<T> for Result<T, vk::Result> {
+    fn context(self, step: &'static str) -> Result<T, FaeError> {
+        self.map_err(|result| FaeError::Vulkan { step, result })
+    }
+}
+
+impl<T> Context<T> for Result<T, vk_mem::error::Error> {
+    fn context(self, step: &'static str) -> Result<T, FaeError> {
+        self.map_err(|error| FaeError::Allocation { step, error })
+    }
+}
@@ -1,127 +1,202 @@
 use crate::*;
 
+// outcome of presenting a frame, the swapchain should be recreated for Suboptimal and OutOfDate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameResult {
+    Success,
+    // the frame was presented but the swapchain no longer matches the surface exactly
+    Suboptimal,
+    // the swapchain can not be used anymore, nothing was drawn
+    OutOfDate,
+    // the device was lost and everything was rebuilt, nothing was drawn
+    DeviceRecovered,
+}
+
+// where Fae::inject_device_lost makes the renderer fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultPoint {
+    FenceWait,
+    Submit,
+    // only reached when rendering to a window
+    Present,
+}
+
 pub struct Fae {
-    pub window: winit::window::Window,
+    pub window: Option<winit::window::Window>,
     _entry: ash::Entry,
     instance: ash::Instance,
-    debug: std::mem::ManuallyDrop<FaeDebug>,
-    surfaces: std::mem::ManuallyDrop<FaeSurface>,
-    _physical_device: vk::PhysicalDevice,
-    _physical_device_properties: vk::PhysicalDeviceProperties,
-    _queue_families: QueueFamilies,
+    // None when debug utils is not available
+    debug: std::mem::ManuallyDrop<Option<FaeDebug>>,
+    surfaces: std::mem::ManuallyDrop<Option<FaeSurface>>,
+    physical_device: vk::PhysicalDevice,
+    physical_device_properties: vk::PhysicalDeviceProperties,
+    queue_families: QueueFamilies,
     pub queues: Queues,
     pub device: ash::Device,
-    pub swapchain: FaeSwapchain,
+    pub swapchain: Option<FaeSwapchain>,
+    pub offscreen: Option<FaeOffscreen>,
     render_pass: vk::RenderPass,
-    pipeline: Pipeline,
+    pipelines: Pipelines,
     pools: Pools,
+    // command buffers, uniform buffers and descriptor sets exist once per frame in flight
     pub command_buffers: Vec<vk::CommandBuffer>,
-    pub allocator: vk_mem::Allocator,
+    frame_sync: FrameSync,
+    // None unless FaeConfig::profiling is set and the queue supports timestamps
+    profiler: Option<FaeProfiler>,
+    // None unless FaeConfig::statistics is set
+    queries: Option<FaeQueries>,
+    // owns the allocator, buffers and images release themselves into it when dropped
+    pub resources: Rc<FaeResources>,
+    // uploads static model data to device local memory, see Model::update_vertex_buffer.
+    // Dropped before the resources are shut down since it keeps a reference to them.
+    pub uploader: std::mem::ManuallyDrop<FaeUploader>,
     pub models: Vec<Model<model::VertexData, InstanceData>>,
-    pub uniform_buffer: Buffer,
+    // None without VK_EXT_memory_budget
+    properties2: Option<vk::KhrGetPhysicalDeviceProperties2Fn>,
+    // what the logical device was created with, to create it again after it was lost
+    setup: DeviceSetup,
+    // set from losing the device until recover_device succeeds, everything created from the
+    // device is destroyed in between
+    device_lost: bool,
+    // see inject_device_lost
+    injected_fault: Option<FaultPoint>,
+    // see reload_shaders
+    shader_watcher: ShaderWatcher,
+    // transient per frame data (camera uniforms and instances), one pair per frame in flight
+    frame_allocators: Vec<FrameAllocators>,
+    // what was written into each frame's allocator for the command buffer to bind
+    frame_data: Vec<FrameData>,
+    uniform_alignment: u64,
     descriptor_pool: vk::DescriptorPool,
     descriptor_sets: Vec<vk::DescriptorSet>,
+    // buffer each frame's descriptor set currently points at
+    descriptor_buffers: Vec<vk::Buffer>,
+    capture_requested: bool,
+    captured_frame: Option<CapturedFrame>,
 }
 
 impl Fae {
-    pub fn init(window: winit::window::Window) -> Result<Fae, Box<dyn std::error::Error>> {
+    pub fn init(window: winit::window::Window) -> Result<Fae, FaeError> {
+        Fae::init_with_config(window, &FaeConfig::default())
+    }
+
+    pub fn init_with_config(
+        window: winit::window::Window,
+        config: &FaeConfig,
+    ) -> Result<Fae, FaeError> {
+        Fae::init_internal(Some(window), vk::Extent2D::default(), config)
+    }
+
+    // renders into offscreen images instead of a swapchain, no window or display needed
+    pub fn init_headless(width: u32, height: u32) -> Result<Fae, FaeError> {
+        Fae::init_headless_with_config(width, height, &FaeConfig::default())
+    }
+
+    pub fn init_headless_with_config(
+        width: u32,
+        height: u32,
+        config: &FaeConfig,
+    ) -> Result<Fae, FaeError> {
+        Fae::init_internal(None, vk::Extent2D { width, height }, config)
+    }
+
+    // offscreen_extent is only used when there is no window
+    fn init_internal(
+        window: Option<winit::window::Window>,
+        offscreen_extent: vk::Extent2D,
+        config: &FaeConfig,
+    ) -> Result<Fae, FaeError> {
+        let frames_in_flight = config.frames_in_flight.max(1);
         // create vulkan entry
         let entry = ash::Entry::new()?;
-        // layer names to enable
-        let layer_names = vec!["VK_LAYER_KHRONOS_validation"];
+        // layer names to enable, empty unless validation was asked for
+        let requested_layer_names = config.layer_names();
+        // surface extensions matching the window's platform
+        let surface_extension_names = match &window {
+            Some(window) => FaeSurface::required_extension_names(window)
+                .map_err(FaeError::surface("finding the surface extensions"))?,
+            None => vec![],
+        };
         // create vulkan instance
-        let instance = init_instance(&entry, &layer_names)?;
+        let (instance, layer_names, optional_extensions) = init_instance(
+            &entry,
+            &requested_layer_names,
+            &surface_extension_names,
+            &config.debug,
+        )?;
         // create debug messenger instance
-        let debug = FaeDebug::init(&entry, &instance)?;
+        let debug = if optional_extensions.debug_utils {
+            Some(
+                FaeDebug::init(&entry, &instance, &config.debug)
+                    .context("creating the debug messenger")?,
+            )
+        } else {
+            None
+        };
         // create surface instance
-        let surfaces = FaeSurface::init(&window, &entry, &instance)?;
+        let surfaces = match &window {
+            Some(window) => Some(
+                FaeSurface::init(window, &entry, &instance)
+                    .map_err(FaeError::surface("creating the surface"))?,
+            ),
+            None => None,
+        };
+        // swapchain extension is only needed when presenting to a window
+        let mut device_extension_names = required_device_extension_names(surfaces.as_ref());
         // init physical rendering device and properties
-        let (physical_device, physical_device_properties, _physical_device_features) =
-            init_physical_device_and_properties(&instance)?;
-        // create queue family instance
-        let queue_families = QueueFamilies::init(&instance, physical_device, &surfaces)?;
-        // create logical device and device queues
-        let (logical_device, queues) =
-            init_device_and_queues(&instance, physical_device, &queue_families, &layer_names)?;
-        // create allocator
-        let allocator_create_info = vk_mem::AllocatorCreateInfo {
-            physical_device,
-            device: logical_device.clone(),
-            instance: instance.clone(),
+        let (physical_device, physical_device_properties, physical_device_features, queue_families) =
+            init_physical_device_and_properties(
+                &instance,
+                surfaces.as_ref(),
+                &device_extension_names,
+                &config.device,
+            )?;
+        // the wireframe pipeline needs non solid fill modes, query features are only enabled
+        // when asked for
+        let enabled_features = vk::PhysicalDeviceFeatures {
+            fill_mode_non_solid: vk::TRUE,
+            pipeline_statistics_query: if config.statistics {
+                physical_device_features.pipeline_statistics_query
+            } else {
+                vk::FALSE
+            },
+            occlusion_query_precise: if config.statistics {
+                physical_device_features.occlusion_query_precise
+            } else {
+                vk::FALSE
+            },
             ..Default::default()
         };
-        let allocator = vk_mem::Allocator::new(&allocator_create_info)?;
-
-        let _allocation_create_info = vk_mem::AllocationCreateInfo {
-            usage: vk_mem::MemoryUsage::CpuToGpu,
-            ..Default::default()
+        // memory budget is optional and needs physical device properties2 on the instance
+        let memory_budget = optional_extensions.physical_device_properties2
+            && supports_device_extension(&instance, physical_device, vk::ExtMemoryBudgetFn::name())
+                .context("listing the device extensions")?;
+        let properties2 = if memory_budget {
+            device_extension_names.push(vk::ExtMemoryBudgetFn::name());
+            Some(vk::KhrGetPhysicalDeviceProperties2Fn::load(|name| unsafe {
+                std::mem::transmute(entry.get_instance_proc_addr(instance.handle(), name.as_ptr()))
+            }))
+        } else {
+            None
         };
-        // create swapchain
-        let mut swapchain = FaeSwapchain::init(
+        let setup = DeviceSetup {
+            layer_names,
+            extension_names: device_extension_names,
+            features: enabled_features,
+            offscreen_extent,
+            config: config.clone(),
+            pipelines: render_pass_and_pipeline::default_pipelines(),
+        };
+        // create the logical device and everything built from it
+        let objects = create_device_objects(
             &instance,
             physical_device,
-            &logical_device,
-            &surfaces,
             &queue_families,
-            &allocator,
+            surfaces.as_ref(),
+            window.as_ref(),
+            debug.as_ref(),
+            &setup,
         )?;
-        // create render pass
-        let render_pass = init_render_pass(&logical_device, swapchain.surface_format.format)?;
-        // create framebuffers
-        swapchain.create_framebuffers(&logical_device, render_pass)?;
-        // create pipeline
-        let pipeline = Pipeline::init(&logical_device, &swapchain, &render_pass)?;
-        // create command pools
-        let pools = Pools::init(&logical_device, &queue_families)?;
-        // create command buffers
-        let command_buffers =
-            create_command_buffers(&logical_device, &pools, swapchain.amount_of_images)?;
-
-        // create uniform buffer
-        let mut uniform_buffer = Buffer::new(
-            &allocator,
-            128,
-            vk::BufferUsageFlags::UNIFORM_BUFFER,
-            vk_mem::MemoryUsage::CpuToGpu,
-        )?;
-        let camera_transform: [[[f32; 4]; 4]; 2] = [
-            nalgebra::Matrix4::identity().into(),
-            nalgebra::Matrix4::identity().into(),
-        ];
-        uniform_buffer.fill(&allocator, &camera_transform)?;
-
-        let pool_sizes = [vk::DescriptorPoolSize {
-            ty: vk::DescriptorType::UNIFORM_BUFFER,
-            descriptor_count: swapchain.amount_of_images,
-        }];
-        let descriptor_pool_info = vk::DescriptorPoolCreateInfo::builder()
-            .max_sets(swapchain.amount_of_images)
-            .pool_sizes(&pool_sizes);
-        let descriptor_pool =
-            unsafe { logical_device.create_descriptor_pool(&descriptor_pool_info, None) }?;
-
-        let desc_layouts =
-            vec![pipeline.descriptor_set_layouts[0]; swapchain.amount_of_images as usize];
-        let descriptor_set_allocate_info = vk::DescriptorSetAllocateInfo::builder()
-            .descriptor_pool(descriptor_pool)
-            .set_layouts(&desc_layouts);
-        let descriptor_sets =
-            unsafe { logical_device.allocate_descriptor_sets(&descriptor_set_allocate_info) }?;
-
-        for descset in descriptor_sets.iter() {
-            let buffer_infos = [vk::DescriptorBufferInfo {
-                buffer: uniform_buffer.buffer,
-                offset: 0,
-                range: 128,
-            }];
-            let desc_sets_write = [vk::WriteDescriptorSet::builder()
-                .dst_set(*descset)
-                .dst_binding(0)
-                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-                .buffer_info(&buffer_infos)
-                .build()];
-            unsafe { logical_device.update_descriptor_sets(&desc_sets_write, &[]) };
-        }
 
         Ok(Fae {
             window,
@@ -129,30 +204,742 @@ impl Fae {
             instance,
             debug: std::mem::ManuallyDrop::new(debug),
             surfaces: std::mem::ManuallyDrop::new(surfaces),
-            _physical_device: physical_device,
-            _physical_device_properties: physical_device_properties,
-            _queue_families: queue_families,
-            queues,
-            device: logical_device,
-            swapchain,
-            render_pass,
-            pipeline,
-            pools,
-            command_buffers,
-            allocator,
+            physical_device,
+            physical_device_properties,
+            queue_families,
+            queues: objects.queues,
+            device: objects.device,
+            swapchain: objects.swapchain,
+            offscreen: objects.offscreen,
+            render_pass: objects.render_pass,
+            pipelines: objects.pipelines,
+            pools: objects.pools,
+            command_buffers: objects.command_buffers,
+            frame_sync: objects.frame_sync,
+            profiler: objects.profiler,
+            queries: objects.queries,
+            resources: objects.resources,
+            uploader: std::mem::ManuallyDrop::new(objects.uploader),
             models: vec![],
-            uniform_buffer,
-            descriptor_pool,
-            descriptor_sets,
+            properties2,
+            setup,
+            device_lost: false,
+            injected_fault: None,
+            shader_watcher: ShaderWatcher::default(),
+            frame_allocators: objects.frame_allocators,
+            frame_data: (0..frames_in_flight)
+                .map(|_| FrameData::default())
+                .collect(),
+            uniform_alignment: physical_device_properties
+                .limits
+                .min_uniform_buffer_offset_alignment,
+            descriptor_pool: objects.descriptor_pool,
+            descriptor_sets: objects.descriptor_sets,
+            descriptor_buffers: objects.descriptor_buffers,
+            capture_requested: false,
+            captured_frame: None,
         })
     }
 
-    pub fn update_command_buffer(&mut self, index: usize) -> Result<(), vk::Result> {
-        let command_buffer = self.command_buffers[index];
+    // zero while there is nothing to render into, like during a failed device recovery
+    pub fn extent(&self) -> vk::Extent2D {
+        match (&self.swapchain, &self.offscreen) {
+            (Some(swapchain), _) => swapchain.extent,
+            (None, Some(offscreen)) => offscreen.extent,
+            (None, None) => vk::Extent2D {
+                width: 0,
+                height: 0,
+            },
+        }
+    }
+
+    pub fn device_name(&self) -> String {
+        unsafe { std::ffi::CStr::from_ptr(self.physical_device_properties.device_name.as_ptr()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    // every physical device with the reasons it was accepted or rejected
+    pub fn physical_device_candidates(&self) -> Result<Vec<DeviceCandidate>, FaeError> {
+        list_physical_devices(
+            &self.instance,
+            self.surfaces.as_ref(),
+            &required_device_extension_names(self.surfaces.as_ref()),
+        )
+        .context("rating the physical devices")
+    }
+
+    // gives a vulkan object a name for validation messages and debugging tools like renderdoc,
+    // does nothing without debug utils
+    pub fn set_object_name<H: vk::Handle>(&self, handle: H, name: &str) {
+        if let Some(debug) = self.debug.as_ref() {
+            debug.set_object_name(&self.device, handle, name);
+        }
+    }
+
+    // gpu timings, None unless profiling was enabled in the config and is supported
+    pub fn profiler(&self) -> Option<&FaeProfiler> {
+        self.profiler.as_ref()
+    }
+
+    pub fn profiler_mut(&mut self) -> Option<&mut FaeProfiler> {
+        self.profiler.as_mut()
+    }
+
+    // vertex, primitive, clipping and fragment counts plus visible samples per model of the
+    // most recent frame the gpu finished, None unless FaeConfig::statistics is set
+    pub fn frame_statistics(&self) -> Option<&FrameStatistics> {
+        self.queries
+            .as_ref()
+            .and_then(|queries| queries.last_frame())
+    }
+
+    // vk-mem statistics by heap and memory type, live resources by category and, with
+    // VK_EXT_memory_budget, the budget of every heap
+    pub fn memory_report(&self) -> Result<MemoryReport, FaeError> {
+        let budgets = self
+            .properties2
+            .as_ref()
+            .map(|properties2| memory_report::query_budgets(properties2, self.physical_device));
+        let mut report = MemoryReport::collect(
+            &self.instance,
+            self.physical_device,
+            &self.resources,
+            budgets,
+        )
+        .context("calculating the memory statistics")?;
+        // the frame before the current one is the last one that was prepared
+        let frames = self.frame_data.len();
+        let last = &self.frame_data[(self.frame_sync.current_frame + frames - 1) % frames];
+        report.frame_uniform_bytes = last.cameras.iter().map(|(slice, _)| slice.size).sum();
+        report.frame_instance_bytes = last.instances.iter().flatten().map(|s| s.size).sum();
+        Ok(report)
+    }
+
+    // validation errors reported so far, always 0 without debug utils
+    pub fn validation_error_count(&self) -> usize {
+        self.debug.as_ref().map_or(0, |debug| debug.error_count())
+    }
+
+    // format and color space the swapchain images were created with, None when headless
+    pub fn surface_format(&self) -> Option<vk::SurfaceFormatKHR> {
+        self.swapchain
+            .as_ref()
+            .map(|swapchain| swapchain.surface_format)
+    }
+
+    // None when headless
+    pub fn present_mode(&self) -> Option<vk::PresentModeKHR> {
+        self.swapchain
+            .as_ref()
+            .map(|swapchain| swapchain.present_mode)
+    }
+
+    // true while the window has no area to draw into, nothing should be rendered then
+    pub fn is_minimised(&self) -> bool {
+        match &self.window {
+            Some(window) => {
+                let extent = window_extent(window);
+                extent.width == 0 || extent.height == 0
+            }
+            None => false,
+        }
+    }
+
+    // rebuilds the swapchain and everything depending on its size after a resize or
+    // an out of date swapchain, must not be called while the window is minimised
+    pub fn recreate_swapchain(&mut self) -> Result<(), FaeError> {
+        let (swapchain, surfaces, window) =
+            match (&mut self.swapchain, &*self.surfaces, &self.window) {
+                (Some(swapchain), Some(surfaces), Some(window)) => (swapchain, surfaces, window),
+                _ => return Ok(()),
+            };
+        unsafe { self.device.device_wait_idle() }
+            .context("waiting for the device before recreating the swapchain")?;
+        swapchain.recreate(
+            self.physical_device,
+            &self.device,
+            surfaces,
+            &self.queue_families,
+            &self.resources,
+            window_extent(window),
+            self.render_pass,
+            self.debug.as_ref(),
+        )?;
+        // viewport and scissor are dynamic, the pipelines work with any size
+        self.frame_sync.reset_images(swapchain.amount_of_images);
+        Ok(())
+    }
+
+    fn framebuffer(&self, index: usize) -> Result<vk::Framebuffer, FaeError> {
+        match (&self.swapchain, &self.offscreen) {
+            (Some(swapchain), _) => Ok(swapchain.framebuffers[index]),
+            (None, Some(offscreen)) => Ok(offscreen.framebuffer),
+            (None, None) => Err(FaeError::NoRenderTarget),
+        }
+    }
+
+    // updates the camera and instance data, then renders and presents one frame.
+    // Headless renderers render into the offscreen images and report Success unless the
+    // device was lost.
+    pub fn draw_frame(&mut self, camera: &Camera) -> Result<FrameResult, FaeError> {
+        self.draw_views(&[View {
+            camera,
+            rect: ViewRect::FULL,
+        }])
+    }
+
+    // like draw_frame, but every model is drawn once per view, each with its own camera into
+    // its own part of the frame. Later views are drawn over earlier ones, so picture in picture
+    // lists the main view first.
+    pub fn draw_views(&mut self, views: &[View]) -> Result<FrameResult, FaeError> {
+        // an earlier recovery failed, try again before using the destroyed device objects
+        if self.device_lost {
+            self.recover_device()?;
+        }
+        match self.draw_frame_on_device(views) {
+            Err(e) if e.is_device_lost() => {
+                log::error!("{}, rebuilding the device", e);
+                self.recover_device()?;
+                Ok(FrameResult::DeviceRecovered)
+            }
+            result => result,
+        }
+    }
+
+    fn draw_frame_on_device(&mut self, views: &[View]) -> Result<FrameResult, FaeError> {
+        // errors reported while drawing the previous frames
+        if let Some(debug) = self.debug.as_ref() {
+            debug.check_errors();
+        }
+        if self.swapchain.is_none() {
+            self.render_offscreen_views(views)?;
+            self.capture_if_requested(0)?;
+            return Ok(FrameResult::Success);
+        }
+        let frame = self.frame_sync.current_frame;
+        let image_available = self.frame_sync.image_available[frame];
+        let rendering_finished = self.frame_sync.rendering_finished[frame];
+        let in_flight = self.frame_sync.in_flight[frame];
+        // wait until the gpu is done with the last use of this frame's resources
+        self.injected_fault(FaultPoint::FenceWait)
+            .and_then(|_| unsafe {
+                self.device
+                    .wait_for_fences(&[in_flight], true, std::u64::MAX)
+            })
+            .context("waiting for the frame fence")?;
+        // the queries of that use are done as well
+        if let Some(profiler) = &mut self.profiler {
+            profiler.collect(&self.device, frame);
+        }
+        if let Some(queries) = &mut self.queries {
+            queries.collect(&self.device, frame);
+        }
+        // aquire the next image
+        let swapchain = self.swapchain.as_ref().ok_or(FaeError::NoRenderTarget)?;
+        let (image_index, acquire_suboptimal) = match unsafe {
+            swapchain.swapchain_loader.acquire_next_image(
+                swapchain.swapchain,
+                std::u64::MAX,
+                image_available,
+                vk::Fence::null(),
+            )
+        } {
+            Ok(acquired) => acquired,
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => return Ok(FrameResult::OutOfDate),
+            Err(result) => {
+                return Err(FaeError::Surface {
+                    step: "acquiring the next swapchain image",
+                    result,
+                })
+            }
+        };
+        // an earlier frame may still be rendering into this image
+        let image_in_flight = self.frame_sync.images_in_flight[image_index as usize];
+        if image_in_flight != vk::Fence::null() {
+            self.injected_fault(FaultPoint::FenceWait)
+                .and_then(|_| unsafe {
+                    self.device
+                        .wait_for_fences(&[image_in_flight], true, std::u64::MAX)
+                })
+                .context("waiting for the image fence")?;
+        }
+        self.frame_sync.images_in_flight[image_index as usize] = in_flight;
+        self.update_frame_data(views, frame)?;
+        //update command buffer
+        self.update_command_buffer(frame, image_index as usize)?;
+        // command buffer setup info
+        let semaphores_available = [image_available];
+        let waiting_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
+        let semaphores_finished = [rendering_finished];
+        let command_buffers = [self.command_buffers[frame]];
+        let submit_info = [vk::SubmitInfo::builder()
+            .wait_semaphores(&semaphores_available)
+            .wait_dst_stage_mask(&waiting_stages)
+            .command_buffers(&command_buffers)
+            .signal_semaphores(&semaphores_finished)
+            .build()];
+        // only reset once nothing can fail before the submit, an unsignaled fence that is never
+        // submitted would block the next wait on this frame forever
+        unsafe { self.device.reset_fences(&[in_flight]) }.context("resetting the frame fence")?;
+        // submit command buffer
+        self.injected_fault(FaultPoint::Submit)
+            .and_then(|_| unsafe {
+                self.device
+                    .queue_submit(self.queues.graphics_queue, &submit_info, in_flight)
+            })
+            .context("submitting the frame")?;
+        self.frame_sync.advance();
+        // take the screenshot before the image is handed back to the presentation engine
+        self.capture_if_requested(image_index as usize)?;
+        // present image to screen
+        let injected = self.injected_fault(FaultPoint::Present);
+        let swapchain = self.swapchain.as_ref().ok_or(FaeError::NoRenderTarget)?;
+        let swapchains = [swapchain.swapchain];
+        let indices = [image_index];
+        let present_info = vk::PresentInfoKHR::builder()
+            .wait_semaphores(&semaphores_finished)
+            .swapchains(&swapchains)
+            .image_indices(&indices);
+        match injected.and_then(|_| unsafe {
+            swapchain
+                .swapchain_loader
+                .queue_present(self.queues.graphics_queue, &present_info)
+        }) {
+            Ok(present_suboptimal) if present_suboptimal || acquire_suboptimal => {
+                Ok(FrameResult::Suboptimal)
+            }
+            Ok(_) => Ok(FrameResult::Success),
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => Ok(FrameResult::OutOfDate),
+            Err(result) => Err(FaeError::Surface {
+                step: "presenting the frame",
+                result,
+            }),
+        }
+    }
+
+    // builds a pipeline that models can select by name, WIREFRAME_PIPELINE and SOLID_PIPELINE
+    // exist from the start. A pipeline with the same name is replaced and destroyed once the
+    // frames in flight are done with it.
+    pub fn add_pipeline(&mut self, name: &str, builder: PipelineBuilder) -> Result<(), FaeError> {
+        let replaced = self.pipelines.insert(
+            &self.device,
+            self.render_pass,
+            name,
+            builder,
+            self.debug.as_ref(),
+        )?;
+        if let Some(pipeline) = replaced {
+            self.resources.release_pipeline(pipeline);
+        }
+        Ok(())
+    }
+
+    // builds the pipelines from PipelineBuilder::shader_files again when a file in the
+    // directory of their shaders changed, call it between frames. Ok(true) if any were
+    // rebuilt. A pipeline whose shaders fail to compile keeps running as it was, the first
+    // error is returned and the others are logged.
+    pub fn reload_shaders(&mut self) -> Result<bool, FaeError> {
+        let builders = self.pipelines.builders();
+        let mut directories: Vec<std::path::PathBuf> = vec![];
+        for (_, builder) in &builders {
+            for directory in builder.shader_directories() {
+                if !directories.contains(&directory) {
+                    directories.push(directory);
+                }
+            }
+        }
+        let changed = self.shader_watcher.changed_directories(&directories);
+        if changed.is_empty() {
+            return Ok(false);
+        }
+        let mut result = Ok(true);
+        for (name, mut builder) in builders {
+            if !builder
+                .shader_directories()
+                .iter()
+                .any(|directory| changed.contains(directory))
+            {
+                continue;
+            }
+            let rebuilt = builder
+                .load_shaders()
+                .and_then(|_| self.add_pipeline(&name, builder));
+            match rebuilt {
+                Ok(()) => log::info!("reloaded the shaders of the {} pipeline", name),
+                Err(e) if result.is_ok() => result = Err(e),
+                Err(e) => log::error!("keeping the old {} pipeline: {}", name, e),
+            }
+        }
+        result
+    }
+
+    // the next time the renderer gets to the given point it fails with VK_ERROR_DEVICE_LOST
+    // instead of calling vulkan, to test the recovery without a real driver reset
+    pub fn inject_device_lost(&mut self, at: FaultPoint) {
+        self.injected_fault = Some(at);
+    }
+
+    fn injected_fault(&mut self, point: FaultPoint) -> Result<(), vk::Result> {
+        if self.injected_fault == Some(point) {
+            self.injected_fault = None;
+            return Err(vk::Result::ERROR_DEVICE_LOST);
+        }
+        Ok(())
+    }
+
+    // rebuilds the logical device and everything created from it after VK_ERROR_DEVICE_LOST,
+    // draw_frame does this by itself. The models in `models` get their buffers uploaded again
+    // from their vertex and index data, buffers and images kept anywhere else are invalid
+    // afterwards. If this fails it can be called again.
+    pub fn recover_device(&mut self) -> Result<(), FaeError> {
+        if !self.device_lost {
+            self.device_lost = true;
+            // waiting fails on a lost device, destroying everything is still allowed
+            if let Err(e) = unsafe { self.device.device_wait_idle() } {
+                log::warn!("waiting for the lost device failed: {}", e);
+            }
+            // pipelines added since the device was created are built again as well
+            self.setup.pipelines = self.pipelines.builders();
+            // the new profiler continues the averages and csv output of this one
+            let profiler = self.profiler.take();
+            unsafe {
+                if let Some(profiler) = &profiler {
+                    profiler.destroy_queries(&self.device);
+                }
+                self.destroy_device_objects();
+            }
+            self.profiler = profiler;
+        }
+        let objects = create_device_objects(
+            &self.instance,
+            self.physical_device,
+            &self.queue_families,
+            self.surfaces.as_ref(),
+            self.window.as_ref(),
+            self.debug.as_ref(),
+            &self.setup,
+        )?;
+        self.device = objects.device;
+        self.queues = objects.queues;
+        self.queries = objects.queries;
+        self.resources = objects.resources;
+        self.swapchain = objects.swapchain;
+        self.offscreen = objects.offscreen;
+        self.render_pass = objects.render_pass;
+        self.pipelines = objects.pipelines;
+        self.pools = objects.pools;
+        // the old uploader was dropped with the device
+        self.uploader = std::mem::ManuallyDrop::new(objects.uploader);
+        self.command_buffers = objects.command_buffers;
+        self.frame_sync = objects.frame_sync;
+        self.profiler = match (objects.profiler, self.profiler.take()) {
+            (Some(mut profiler), Some(lost)) => {
+                profiler.carry_over(lost);
+                Some(profiler)
+            }
+            (profiler, _) => profiler,
+        };
+        self.frame_allocators = objects.frame_allocators;
+        self.frame_data = (0..self.frame_allocators.len())
+            .map(|_| FrameData::default())
+            .collect();
+        self.descriptor_pool = objects.descriptor_pool;
+        self.descriptor_sets = objects.descriptor_sets;
+        self.descriptor_buffers = objects.descriptor_buffers;
+        self.device_lost = false;
+        for m in &mut self.models {
+            m.update_vertex_buffer(&self.uploader)?;
+            m.update_index_buffer(&self.uploader)?;
+        }
+        Ok(())
+    }
+
+    // destroys everything created from the logical device and the device itself, the device
+    // has to be idle or lost. Models keep their vertex and index data but lose their buffers.
+    unsafe fn destroy_device_objects(&mut self) {
+        // release everything that was allocated through the resources
+        self.frame_allocators.clear();
+        for m in &mut self.models {
+            m.vertex_buffer = None;
+            m.index_buffer = None;
+        }
+        std::mem::ManuallyDrop::drop(&mut self.uploader);
+        self.frame_sync.cleanup(&self.device);
+        if let Some(profiler) = &mut self.profiler {
+            profiler.cleanup(&self.device);
+        }
+        if let Some(queries) = &self.queries {
+            queries.cleanup(&self.device);
+        }
+        self.pools.cleanup(&self.device);
+        self.pipelines.cleanup(&self.device);
+        self.device.destroy_render_pass(self.render_pass, None);
+        self.device
+            .destroy_descriptor_pool(self.descriptor_pool, None);
+        if let Some(mut swapchain) = self.swapchain.take() {
+            swapchain.cleanup(&self.device);
+        }
+        if let Some(mut offscreen) = self.offscreen.take() {
+            offscreen.cleanup(&self.device);
+        }
+        // destroys what was released above and reports anything still alive in debug builds
+        FaeResources::shutdown(&mut self.resources);
+        self.device.destroy_device(None);
+    }
+
+    // the next frame drawn with draw_frame is copied out, get it with take_capture. Fails
+    // right away if the surface does not allow copying or uses a format that can not be read.
+    pub fn request_capture(&mut self) -> Result<(), FaeError> {
+        self.check_capturable()?;
+        self.capture_requested = true;
+        Ok(())
+    }
+
+    // swapchain images can only be copied from if the surface allows TRANSFER_SRC, and the
+    // surface format may be one CapturedFrame can not read
+    fn check_capturable(&self) -> Result<(), FaeError> {
+        let (usage, format) = match (&self.swapchain, &self.offscreen) {
+            (Some(swapchain), _) => (swapchain.image_usage, swapchain.surface_format.format),
+            // created with TRANSFER_SRC
+            (None, Some(offscreen)) => (vk::ImageUsageFlags::TRANSFER_SRC, offscreen.format),
+            (None, None) => return Ok(()),
+        };
+        if !usage.contains(vk::ImageUsageFlags::TRANSFER_SRC) {
+            return Err(FaeError::CaptureNotSupported(
+                "the surface does not allow copying from its images".to_string(),
+            ));
+        }
+        if !capture::is_capturable(format) {
+            return Err(FaeError::CaptureNotSupported(format!(
+                "{:?} is not an 8 bit RGBA or BGRA format",
+                format
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn take_capture(&mut self) -> Option<CapturedFrame> {
+        self.captured_frame.take()
+    }
+
+    fn capture_if_requested(&mut self, image_index: usize) -> Result<(), FaeError> {
+        if self.capture_requested {
+            self.capture_requested = false;
+            self.captured_frame = Some(self.capture_frame(image_index)?);
+        }
+        Ok(())
+    }
+
+    // only touches memory owned by the given frame, so earlier frames can still be in flight.
+    // The frame's fence has to be waited on first.
+    fn update_frame_data(&mut self, views: &[View], frame: usize) -> Result<(), FaeError> {
+        self.resources.begin_frame(
+            self.frame_sync.frame_number,
+            self.frame_sync.completed_frames(),
+        )?;
+        let extent = self.extent();
+        let frame_allocators = &mut self.frame_allocators[frame];
+        frame_allocators.uniforms.reset();
+        frame_allocators.instances.reset();
+        // the cameras of all views are pushed together so they end up in the same buffer, the
+        // descriptor set can only point at one. Each starts at a valid dynamic offset.
+        let camera_size = std::mem::size_of::<[[[f32; 4]; 4]; 2]>() as u64;
+        let stride = (camera_size + self.uniform_alignment - 1) / self.uniform_alignment
+            * self.uniform_alignment;
+        let floats_per_camera = (stride / 4) as usize;
+        let mut camera_data = vec![0.0f32; floats_per_camera * views.len()];
+        for (view, data) in views.iter().zip(camera_data.chunks_mut(floats_per_camera)) {
+            // the projection follows the size of the view, also after the swapchain was resized
+            let uniform_data = view.camera.uniform_data_for(view.rect.extent(extent));
+            let floats = uniform_data.iter().flatten().flatten();
+            for (target, value) in data.iter_mut().zip(floats) {
+                *target = *value;
+            }
+        }
+        let cameras_slice = frame_allocators
+            .uniforms
+            .push(&camera_data, self.uniform_alignment)?;
+        let cameras: Vec<(TransientSlice, ViewRect)> = views
+            .iter()
+            .enumerate()
+            .map(|(i, view)| {
+                let slice = TransientSlice {
+                    buffer: cameras_slice.buffer,
+                    offset: cameras_slice.offset + i as u64 * stride,
+                    size: camera_size,
+                };
+                (slice, view.rect)
+            })
+            .collect();
+        let mut instances = Vec::with_capacity(self.models.len());
+        for m in &self.models {
+            if self.pipelines.get(&m.pipeline).is_none() {
+                return Err(FaeError::UnknownPipeline(m.pipeline.clone()));
+            }
+            instances.push(m.push_instances(&mut frame_allocators.instances)?);
+        }
+        // the allocator grew into a new buffer
+        if cameras_slice.buffer != self.descriptor_buffers[frame] {
+            write_camera_descriptor(
+                &self.device,
+                self.descriptor_sets[frame],
+                cameras_slice.buffer,
+            );
+            self.descriptor_buffers[frame] = cameras_slice.buffer;
+        }
+        self.frame_data[frame] = FrameData { cameras, instances };
+        Ok(())
+    }
+
+    // record, submit and wait for a single frame into the offscreen images. There is only one
+    // color image, so the frame is finished before this returns.
+    pub fn render_offscreen(&mut self, camera: &Camera) -> Result<(), FaeError> {
+        self.render_offscreen_views(&[View {
+            camera,
+            rect: ViewRect::FULL,
+        }])
+    }
+
+    pub fn render_offscreen_views(&mut self, views: &[View]) -> Result<(), FaeError> {
+        if self.offscreen.is_none() {
+            return Err(FaeError::NotOffscreen);
+        }
+        let frame = self.frame_sync.current_frame;
+        let in_flight = self.frame_sync.in_flight[frame];
+        self.injected_fault(FaultPoint::FenceWait)
+            .and_then(|_| unsafe {
+                self.device
+                    .wait_for_fences(&[in_flight], true, std::u64::MAX)
+            })
+            .context("waiting for the frame fence")?;
+        self.update_frame_data(views, frame)?;
+        self.update_command_buffer(frame, 0)?;
+        let command_buffers = [self.command_buffers[frame]];
+        let submit_info = [vk::SubmitInfo::builder()
+            .command_buffers(&command_buffers)
+            .build()];
+        // only reset once nothing can fail before the submit, an unsignaled fence that is never
+        // submitted would block the next wait on this frame forever
+        unsafe { self.device.reset_fences(&[in_flight]) }.context("resetting the frame fence")?;
+        self.injected_fault(FaultPoint::Submit)
+            .and_then(|_| unsafe {
+                self.device
+                    .queue_submit(self.queues.graphics_queue, &submit_info, in_flight)
+            })
+            .context("submitting the frame")?;
+        unsafe {
+            self.device
+                .wait_for_fences(&[in_flight], true, std::u64::MAX)
+        }
+        .context("waiting for the frame fence")?;
+        if let Some(profiler) = &mut self.profiler {
+            profiler.collect(&self.device, frame);
+        }
+        if let Some(queries) = &mut self.queries {
+            queries.collect(&self.device, frame);
+        }
+        self.frame_sync.advance();
+        Ok(())
+    }
+
+    // copies the color attachment for the given image into host memory, when rendering to a
+    // window this has to happen after the frame was submitted but before it is presented
+    fn capture_frame(&self, image_index: usize) -> Result<CapturedFrame, FaeError> {
+        self.check_capturable()?;
+        let (image, layout, format) = match (&self.swapchain, &self.offscreen) {
+            (Some(swapchain), _) => (
+                swapchain.images[image_index],
+                vk::ImageLayout::PRESENT_SRC_KHR,
+                swapchain.surface_format.format,
+            ),
+            (None, Some(offscreen)) => (
+                offscreen.color.image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                offscreen.format,
+            ),
+            (None, None) => return Err(FaeError::NoRenderTarget),
+        };
+        let extent = self.extent();
+        let size_in_bytes = extent.width as u64 * extent.height as u64 * 4;
+        let readback_buffer = Buffer::new(
+            &self.resources,
+            "capture readback",
+            size_in_bytes,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            vk_mem::MemoryUsage::GpuToCpu,
+        )?;
+
+        // record and submit a one time copy
+        let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
+            .command_pool(self.pools.command_pool_graphics)
+            .command_buffer_count(1);
+        let command_buffer = unsafe {
+            self.device
+                .allocate_command_buffers(&command_buffer_allocate_info)
+        }
+        .context("allocating the capture command buffer")?[0];
+        let command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        let copied = unsafe {
+            // the frame has to be finished before we can copy it
+            self.device
+                .queue_wait_idle(self.queues.graphics_queue)
+                .context("waiting for the frame to capture")?;
+            self.device
+                .begin_command_buffer(command_buffer, &command_buffer_begin_info)
+                .context("recording the capture copy")?;
+            capture::record_copy_image_to_buffer(
+                &self.device,
+                command_buffer,
+                image,
+                layout,
+                extent,
+                readback_buffer.buffer,
+            );
+            let command_buffers = [command_buffer];
+            let submit_info = [vk::SubmitInfo::builder()
+                .command_buffers(&command_buffers)
+                .build()];
+            let copied = self
+                .device
+                .end_command_buffer(command_buffer)
+                .and_then(|_| {
+                    self.device.queue_submit(
+                        self.queues.graphics_queue,
+                        &submit_info,
+                        vk::Fence::null(),
+                    )
+                })
+                .and_then(|_| self.device.queue_wait_idle(self.queues.graphics_queue));
+            self.device
+                .free_command_buffers(self.pools.command_pool_graphics, &command_buffers);
+            copied
+        };
+        copied.context("copying the captured frame")?;
+
+        let data = readback_buffer.read_bytes();
+        Ok(CapturedFrame::from_raw(
+            extent.width,
+            extent.height,
+            format,
+            data,
+        ))
+    }
+
+    // records the draw commands for a frame in flight into the framebuffer of the given image
+    pub fn update_command_buffer(
+        &mut self,
+        frame: usize,
+        image_index: usize,
+    ) -> Result<(), FaeError> {
+        let command_buffer = self.command_buffers[frame];
+        let framebuffer = self.framebuffer(image_index)?;
         let command_buffer_begin_info = vk::CommandBufferBeginInfo::builder();
         unsafe {
             self.device
-                .begin_command_buffer(command_buffer, &command_buffer_begin_info)?;
+                .begin_command_buffer(command_buffer, &command_buffer_begin_info)
+                .context("recording the command buffer")?;
         }
         let clear_values = [
             vk::ClearValue {
@@ -169,75 +956,447 @@ impl Fae {
         ];
         let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
             .render_pass(self.render_pass)
-            .framebuffer(self.swapchain.framebuffers[index])
+            .framebuffer(framebuffer)
             .render_area(vk::Rect2D {
                 offset: vk::Offset2D { x: 0, y: 0 },
-                extent: self.swapchain.extent,
+                extent: self.extent(),
             })
             .clear_values(&clear_values);
+        let render_pass_region = match &mut self.profiler {
+            Some(profiler) => {
+                profiler.begin_frame(&self.device, command_buffer, frame);
+                profiler.begin_region(&self.device, command_buffer, frame, "render pass")
+            }
+            None => None,
+        };
+        if let Some(queries) = &mut self.queries {
+            queries.begin_frame(&self.device, command_buffer, frame);
+        }
         unsafe {
             self.device.cmd_begin_render_pass(
                 command_buffer,
                 &render_pass_begin_info,
                 vk::SubpassContents::INLINE,
             );
-            self.device.cmd_bind_pipeline(
-                command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline.pipeline,
-            );
-            self.device.cmd_bind_descriptor_sets(
-                command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline.layout,
-                0,
-                &[self.descriptor_sets[index]],
-                &[],
-            );
-            for m in &self.models {
-                m.draw(&self.device, command_buffer);
+            let extent = self.extent();
+            let views = self.frame_data[frame].cameras.clone();
+            for (v, (camera, rect)) in views.iter().enumerate() {
+                let scissor = rect.scissor(extent);
+                if scissor.extent.width == 0 || scissor.extent.height == 0 {
+                    continue;
+                }
+                self.device
+                    .cmd_set_viewport(command_buffer, 0, &[rect.viewport(extent)]);
+                self.device.cmd_set_scissor(command_buffer, 0, &[scissor]);
+                // the render pass cleared the whole frame for the first view, later ones may
+                // overlap what was drawn before
+                if v > 0 {
+                    let clear_attachments = [
+                        vk::ClearAttachment {
+                            aspect_mask: vk::ImageAspectFlags::COLOR,
+                            color_attachment: 0,
+                            clear_value: clear_values[0],
+                        },
+                        vk::ClearAttachment {
+                            aspect_mask: vk::ImageAspectFlags::DEPTH,
+                            color_attachment: 0,
+                            clear_value: clear_values[1],
+                        },
+                    ];
+                    let clear_rects = [vk::ClearRect {
+                        rect: scissor,
+                        base_array_layer: 0,
+                        layer_count: 1,
+                    }];
+                    self.device.cmd_clear_attachments(
+                        command_buffer,
+                        &clear_attachments,
+                        &clear_rects,
+                    );
+                }
+                // every pipeline has the same layout, so the set stays bound when switching
+                self.device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.pipelines.layout,
+                    0,
+                    &[self.descriptor_sets[frame]],
+                    &[camera.offset as u32],
+                );
+                let mut bound_pipeline = vk::Pipeline::null();
+                for (i, m) in self.models.iter().enumerate() {
+                    // update_frame_data made sure it exists
+                    let pipeline = match self.pipelines.get(&m.pipeline) {
+                        Some(pipeline) => pipeline,
+                        None => continue,
+                    };
+                    if pipeline != bound_pipeline {
+                        self.device.cmd_bind_pipeline(
+                            command_buffer,
+                            vk::PipelineBindPoint::GRAPHICS,
+                            pipeline,
+                        );
+                        bound_pipeline = pipeline;
+                    }
+                    let name = if v == 0 {
+                        format!("model {}", i)
+                    } else {
+                        format!("view {} model {}", v, i)
+                    };
+                    // shows up as a region around the draw in renderdoc and validation messages
+                    if let Some(debug) = self.debug.as_ref() {
+                        debug.begin_label(command_buffer, &name, [0.2, 0.6, 1.0, 1.0]);
+                    }
+                    let region = match &mut self.profiler {
+                        Some(profiler) => {
+                            profiler.begin_region(&self.device, command_buffer, frame, &name)
+                        }
+                        None => None,
+                    };
+                    let query = match &mut self.queries {
+                        Some(queries) => queries.begin_model(&self.device, command_buffer, frame),
+                        None => None,
+                    };
+                    if let Some(Some(instances)) = self.frame_data[frame].instances.get(i) {
+                        m.draw(&self.device, command_buffer, instances);
+                    }
+                    if let Some(queries) = &self.queries {
+                        queries.end_model(&self.device, command_buffer, query);
+                    }
+                    if let Some(profiler) = &mut self.profiler {
+                        profiler.end_region(&self.device, command_buffer, frame, region);
+                    }
+                    if let Some(debug) = self.debug.as_ref() {
+                        debug.end_label(command_buffer);
+                    }
+                }
             }
             self.device.cmd_end_render_pass(command_buffer);
-            self.device.end_command_buffer(command_buffer)?;
+            if let Some(profiler) = &mut self.profiler {
+                profiler.end_region(&self.device, command_buffer, frame, render_pass_region);
+            }
+            self.device
+                .end_command_buffer(command_buffer)
+                .context("recording the command buffer")?;
         }
         Ok(())
     }
 }
 
+// everything created from the logical device, built once in init and again after the device
+// was lost
+struct DeviceObjects {
+    device: ash::Device,
+    queues: Queues,
+    queries: Option<FaeQueries>,
+    resources: Rc<FaeResources>,
+    swapchain: Option<FaeSwapchain>,
+    offscreen: Option<FaeOffscreen>,
+    render_pass: vk::RenderPass,
+    pipelines: Pipelines,
+    pools: Pools,
+    uploader: FaeUploader,
+    command_buffers: Vec<vk::CommandBuffer>,
+    frame_sync: FrameSync,
+    profiler: Option<FaeProfiler>,
+    frame_allocators: Vec<FrameAllocators>,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_sets: Vec<vk::DescriptorSet>,
+    descriptor_buffers: Vec<vk::Buffer>,
+}
+
+struct DeviceSetup {
+    layer_names: Vec<&'static str>,
+    extension_names: Vec<&'static std::ffi::CStr>,
+    features: vk::PhysicalDeviceFeatures,
+    // only used when there is no window
+    offscreen_extent: vk::Extent2D,
+    config: FaeConfig,
+    // updated from the pipelines when the device is lost
+    pipelines: Vec<(String, PipelineBuilder)>,
+}
+
+fn create_device_objects(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    queue_families: &QueueFamilies,
+    surfaces: Option<&FaeSurface>,
+    window: Option<&winit::window::Window>,
+    debug: Option<&FaeDebug>,
+    setup: &DeviceSetup,
+) -> Result<DeviceObjects, FaeError> {
+    let frames_in_flight = setup.config.frames_in_flight.max(1);
+    // create logical device and device queues
+    let (logical_device, queues) = init_device_and_queues(
+        instance,
+        physical_device,
+        queue_families,
+        &setup.layer_names,
+        &setup.extension_names,
+        &setup.features,
+    )
+    .context("creating the logical device")?;
+    // create pipeline statistics and occlusion queries
+    let queries = if setup.config.statistics {
+        Some(
+            FaeQueries::init(
+                &logical_device,
+                frames_in_flight,
+                setup.features.pipeline_statistics_query == vk::TRUE,
+                setup.features.occlusion_query_precise == vk::TRUE,
+            )
+            .context("creating the statistics queries")?,
+        )
+    } else {
+        None
+    };
+    // create allocator
+    let allocator_create_info = vk_mem::AllocatorCreateInfo {
+        physical_device,
+        device: logical_device.clone(),
+        instance: instance.clone(),
+        ..Default::default()
+    };
+    let allocator =
+        vk_mem::Allocator::new(&allocator_create_info).context("creating the allocator")?;
+    let resources = FaeResources::new(&logical_device, allocator, debug);
+
+    // create render target, render pass and framebuffers
+    let mut swapchain = None;
+    let mut offscreen = None;
+    let (render_pass, amount_of_images) = match (surfaces, window) {
+        (Some(surfaces), Some(window)) => {
+            // create swapchain
+            let mut fae_swapchain = FaeSwapchain::init(
+                instance,
+                physical_device,
+                &logical_device,
+                surfaces,
+                queue_families,
+                &resources,
+                window_extent(window),
+                &setup.config.swapchain,
+                debug,
+            )?;
+            // create render pass
+            let render_pass = init_render_pass(
+                &logical_device,
+                fae_swapchain.surface_format.format,
+                vk::ImageLayout::PRESENT_SRC_KHR,
+            )
+            .context("creating the render pass")?;
+            // create framebuffers
+            fae_swapchain.create_framebuffers(&logical_device, render_pass)?;
+            let target = (render_pass, fae_swapchain.amount_of_images);
+            swapchain = Some(fae_swapchain);
+            target
+        }
+        _ => {
+            // create offscreen color and depth images
+            let mut fae_offscreen = FaeOffscreen::init(&resources, setup.offscreen_extent)?;
+            // create render pass, leaving the image ready to be copied out
+            let render_pass = init_render_pass(
+                &logical_device,
+                fae_offscreen.format,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            )
+            .context("creating the render pass")?;
+            // create framebuffer
+            fae_offscreen.create_framebuffer(&logical_device, render_pass)?;
+            let target = (render_pass, 1);
+            offscreen = Some(fae_offscreen);
+            target
+        }
+    };
+    // create pipelines
+    let pipelines = Pipelines::init(&logical_device, render_pass, &setup.pipelines, debug)?;
+    // create command pools
+    let pools =
+        Pools::init(&logical_device, queue_families).context("creating the command pools")?;
+    let uploader = FaeUploader::init(&logical_device, &resources, queue_families, &queues, &pools);
+    // create command buffers
+    let command_buffers = create_command_buffers(&logical_device, &pools, frames_in_flight as u32)
+        .context("allocating the command buffers")?;
+    // create semaphores and fences
+    let frame_sync = FrameSync::init(&logical_device, frames_in_flight, amount_of_images)
+        .context("creating the frame semaphores and fences")?;
+    // create timestamp queries
+    let profiler = if setup.config.profiling {
+        FaeProfiler::init(
+            instance,
+            physical_device,
+            &logical_device,
+            queue_families.graphics_q_index,
+            frames_in_flight,
+        )
+        .context("creating the profiler queries")?
+    } else {
+        None
+    };
+
+    // create the per frame allocators for uniforms and instances
+    let mut frame_allocators = Vec::with_capacity(frames_in_flight);
+    for frame in 0..frames_in_flight {
+        frame_allocators.push(FrameAllocators {
+            uniforms: LinearAllocator::new(
+                &resources,
+                &format!("uniform buffer {}", frame),
+                MemoryCategory::Uniform,
+                16 * 1024,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+            )?,
+            instances: LinearAllocator::new(
+                &resources,
+                &format!("instance buffer {}", frame),
+                MemoryCategory::Instance,
+                64 * 1024,
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+            )?,
+        });
+    }
+    let descriptor_buffers: Vec<vk::Buffer> = frame_allocators
+        .iter()
+        .map(|a| a.uniforms.buffer())
+        .collect();
+
+    let (descriptor_pool, descriptor_sets) = create_descriptor_sets(
+        &logical_device,
+        pipelines.descriptor_set_layouts[0],
+        &descriptor_buffers,
+    )
+    .context("creating the descriptor sets")?;
+
+    // name everything created here for validation messages and renderdoc
+    if let Some(debug) = debug {
+        debug.set_object_name(&logical_device, render_pass, "render pass");
+        debug.set_object_name(&logical_device, descriptor_pool, "descriptor pool");
+        for (frame, (descriptor_set, command_buffer)) in
+            descriptor_sets.iter().zip(&command_buffers).enumerate()
+        {
+            let name = |object: &str| format!("{} {}", object, frame);
+            debug.set_object_name(
+                &logical_device,
+                *descriptor_set,
+                &name("camera descriptor set"),
+            );
+            debug.set_object_name(&logical_device, *command_buffer, &name("command buffer"));
+        }
+        if let Some(offscreen) = &offscreen {
+            debug.set_object_name(&logical_device, offscreen.color.image, "offscreen color");
+        }
+    }
+
+    Ok(DeviceObjects {
+        device: logical_device,
+        queues,
+        queries,
+        resources,
+        swapchain,
+        offscreen,
+        render_pass,
+        pipelines,
+        pools,
+        uploader,
+        command_buffers,
+        frame_sync,
+        profiler,
+        frame_allocators,
+        descriptor_pool,
+        descriptor_sets,
+        descriptor_buffers,
+    })
+}
+
+fn required_device_extension_names(surfaces: Option<&FaeSurface>) -> Vec<&'static std::ffi::CStr> {
+    match surfaces {
+        Some(_) => vec![ash::extensions::khr::Swapchain::name()],
+        None => vec![],
+    }
+}
+
+fn window_extent(window: &winit::window::Window) -> vk::Extent2D {
+    let size = window.inner_size();
+    vk::Extent2D {
+        width: size.width,
+        height: size.height,
+    }
+}
+
+// kept apart so the memory report can tell uniforms from instances
+struct FrameAllocators {
+    uniforms: LinearAllocator,
+    instances: LinearAllocator,
+}
+
+// slices of the per frame allocators used while recording a frame
+#[derive(Default)]
+struct FrameData {
+    // one camera per view with the part of the frame it renders into
+    cameras: Vec<(TransientSlice, ViewRect)>,
+    // one per model, None if the model had no visible instances
+    instances: Vec<Option<TransientSlice>>,
+}
+
+// one descriptor set per frame in flight, each pointing at that frame's uniform buffer
+fn create_descriptor_sets(
+    logical_device: &ash::Device,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    buffers: &[vk::Buffer],
+) -> Result<(vk::DescriptorPool, Vec<vk::DescriptorSet>), vk::Result> {
+    let amount = buffers.len() as u32;
+    let pool_sizes = [vk::DescriptorPoolSize {
+        ty: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+        descriptor_count: amount,
+    }];
+    let descriptor_pool_info = vk::DescriptorPoolCreateInfo::builder()
+        .max_sets(amount)
+        .pool_sizes(&pool_sizes);
+    let descriptor_pool =
+        unsafe { logical_device.create_descriptor_pool(&descriptor_pool_info, None) }?;
+
+    let desc_layouts = vec![descriptor_set_layout; amount as usize];
+    let descriptor_set_allocate_info = vk::DescriptorSetAllocateInfo::builder()
+        .descriptor_pool(descriptor_pool)
+        .set_layouts(&desc_layouts);
+    let descriptor_sets =
+        unsafe { logical_device.allocate_descriptor_sets(&descriptor_set_allocate_info) }?;
+
+    for (descset, buffer) in descriptor_sets.iter().zip(buffers) {
+        write_camera_descriptor(logical_device, *descset, *buffer);
+    }
+    Ok((descriptor_pool, descriptor_sets))
+}
+
+// the offset of the camera data is given when binding the set
+fn write_camera_descriptor(
+    logical_device: &ash::Device,
+    descriptor_set: vk::DescriptorSet,
+    buffer: vk::Buffer,
+) {
+    let buffer_infos = [vk::DescriptorBufferInfo {
+        buffer,
+        offset: 0,
+        range: 128,
+    }];
+    let desc_sets_write = [vk::WriteDescriptorSet::builder()
+        .dst_set(descriptor_set)
+        .dst_binding(0)
+        .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+        .buffer_info(&buffer_infos)
+        .build()];
+    unsafe { logical_device.update_descriptor_sets(&desc_sets_write, &[]) };
+}
+
 impl Drop for Fae {
     fn drop(&mut self) {
         unsafe {
-            self.device
-                .device_wait_idle()
-                .expect("something wrong while waiting");
-            self.allocator
-                .destroy_buffer(self.uniform_buffer.buffer, &self.uniform_buffer.allocation)
-                .unwrap();
-            for m in &self.models {
-                if let Some(vb) = &m.vertex_buffer {
-                    self.allocator
-                        .destroy_buffer(vb.buffer, &vb.allocation)
-                        .expect("problem with buffer destruction")
-                }
-                if let Some(ib) = &m.instance_buffer {
-                    self.allocator
-                        .destroy_buffer(ib.buffer, &ib.allocation)
-                        .expect("problem with buffer destruction");
-                }
-                if let Some(ib) = &m.index_buffer {
-                    self.allocator
-                        .destroy_buffer(ib.buffer, &ib.allocation)
-                        .expect("problem with buffer destruction");
+            // a failed recovery already destroyed the device
+            if !self.device_lost {
+                if let Err(e) = self.device.device_wait_idle() {
+                    log::error!("waiting for the device before shutdown failed: {}", e);
                 }
+                self.destroy_device_objects();
             }
-            self.pools.cleanup(&self.device);
-            self.pipeline.cleanup(&self.device);
-            self.device.destroy_render_pass(self.render_pass, None);
-            self.device
-                .destroy_descriptor_pool(self.descriptor_pool, None);
-            self.swapchain.cleanup(&self.device, &self.allocator);
-            self.allocator.destroy();
-            self.device.destroy_device(None);
             std::mem::ManuallyDrop::drop(&mut self.surfaces);
             std::mem::ManuallyDrop::drop(&mut self.debug);
             self.instance.destroy_instance(None);
@@ -0,0 +1,79 @@
+use ash::{version::DeviceV1_0, vk};
+
+// semaphores and fences for the frames the cpu may record ahead of the gpu
+pub struct FrameSync {
+    pub image_available: Vec<vk::Semaphore>,
+    pub rendering_finished: Vec<vk::Semaphore>,
+    pub in_flight: Vec<vk::Fence>,
+    // fence of the frame that last rendered into each swapchain image, null if none
+    pub images_in_flight: Vec<vk::Fence>,
+    pub current_frame: usize,
+    // counts every frame ever prepared, unlike current_frame it never wraps
+    pub frame_number: u64,
+}
+
+impl FrameSync {
+    pub fn init(
+        logical_device: &ash::Device,
+        frames_in_flight: usize,
+        amount_of_images: u32,
+    ) -> Result<FrameSync, vk::Result> {
+        let mut image_available = vec![];
+        let mut rendering_finished = vec![];
+        let mut in_flight = vec![];
+        let semaphore_create_info = vk::SemaphoreCreateInfo::builder();
+        // use fence to sync cpu and gpu
+        // set fence to signaled state so the first wait on each frame returns immediately
+        let fence_create_info =
+            vk::FenceCreateInfo::builder().flags(vk::FenceCreateFlags::SIGNALED);
+        for _ in 0..frames_in_flight {
+            let semaphore_available =
+                unsafe { logical_device.create_semaphore(&semaphore_create_info, None) }?;
+            let semaphore_finished =
+                unsafe { logical_device.create_semaphore(&semaphore_create_info, None) }?;
+            image_available.push(semaphore_available);
+            rendering_finished.push(semaphore_finished);
+            let fence = unsafe { logical_device.create_fence(&fence_create_info, None) }?;
+            in_flight.push(fence);
+        }
+        Ok(FrameSync {
+            image_available,
+            rendering_finished,
+            in_flight,
+            images_in_flight: vec![vk::Fence::null(); amount_of_images as usize],
+            current_frame: 0,
+            frame_number: 0,
+        })
+    }
+
+    pub fn frames_in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    // the swapchain images changed, none of the new ones is in use yet
+    pub fn reset_images(&mut self, amount_of_images: u32) {
+        self.images_in_flight = vec![vk::Fence::null(); amount_of_images as usize];
+    }
+
+    pub fn advance(&mut self) {
+        self.current_frame = (self.current_frame + 1) % self.frames_in_flight();
+        self.frame_number += 1;
+    }
+
+    // number of frames known to be finished once the current frame's fence was waited on
+    pub fn completed_frames(&self) -> u64 {
+        (self.frame_number + 1).saturating_sub(self.frames_in_flight() as u64)
+    }
+
+    pub unsafe fn cleanup(&self, logical_device: &ash::Device) {
+        for fence in &self.in_flight {
+            logical_device.destroy_fence(*fence, None);
+        }
+        for semaphore in &self.image_available {
+            logical_device.destroy_semaphore(*semaphore, None);
+        }
+        for semaphore in &self.rendering_finished {
+            logical_device.destroy_semaphore(*semaphore, None);
+        }
+    }
+}
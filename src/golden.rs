@@ -0,0 +1,258 @@
+// golden image tests: fixed scenes are rendered headless and compared against the
+// reference pngs in tests/golden. A missing reference fails the test, set FAE_BLESS=1 to
+// write the references, or overwrite them after an intended visual change.
+// On failure the actual image and a diff image are written to target/golden.
+// Without a vulkan device the scene tests are skipped with a message. CI sets
+// FAE_REQUIRE_DEVICE (to anything but 0) so that a missing device fails them instead.
+use crate::*;
+
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 600;
+// largest difference allowed in any channel of a single pixel
+const TOLERANCE: u8 = 2;
+
+fn reference_dir() -> std::path::PathBuf {
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn output_dir() -> std::path::PathBuf {
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("target/golden")
+}
+
+// renders a single model with a default camera in each of the rects, None if there is no
+// vulkan device to use. With a fault the device is lost once and recovered before the
+// captured frame.
+fn render_scene(
+    mut model: Model<VertexData, InstanceData>,
+    rects: &[ViewRect],
+    fault: Option<FaultPoint>,
+) -> Option<CapturedFrame> {
+    // validation runs if the layer is installed, the scene has to render without errors
+    let config = FaeConfig {
+        validation: true,
+        ..FaeConfig::default()
+    };
+    let mut fae = match Fae::init_headless_with_config(WIDTH, HEIGHT, &config) {
+        Ok(fae) => fae,
+        Err(e) if device_required() => {
+            panic!("no headless renderer and FAE_REQUIRE_DEVICE is set: {}", e)
+        }
+        Err(e) => {
+            eprintln!("SKIPPED golden image test, no headless renderer: {}", e);
+            return None;
+        }
+    };
+    let cameras: Vec<Camera> = rects.iter().map(|_| Camera::builder().build()).collect();
+    let views: Vec<View> = cameras
+        .iter()
+        .zip(rects)
+        .map(|(camera, &rect)| View { camera, rect })
+        .collect();
+    model.update_vertex_buffer(&fae.uploader).unwrap();
+    model.update_index_buffer(&fae.uploader).unwrap();
+    fae.models = vec![model];
+    if let Some(fault) = fault {
+        fae.inject_device_lost(fault);
+        assert_eq!(
+            fae.draw_views(&views).unwrap(),
+            FrameResult::DeviceRecovered
+        );
+    }
+    fae.request_capture().unwrap();
+    fae.draw_views(&views).unwrap();
+    assert_eq!(
+        fae.validation_error_count(),
+        0,
+        "validation errors while rendering"
+    );
+    Some(
+        fae.take_capture()
+            .expect("the requested frame was not captured"),
+    )
+}
+
+fn device_required() -> bool {
+    std::env::var("FAE_REQUIRE_DEVICE").map_or(false, |value| !value.is_empty() && value != "0")
+}
+
+// number of pixels where any channel differs by more than TOLERANCE, plus an image
+// highlighting them in red over a dimmed copy of the actual frame
+fn compare(expected: &CapturedFrame, actual: &CapturedFrame) -> (usize, CapturedFrame) {
+    let mut mismatched = 0;
+    let mut diff = Vec::with_capacity(actual.rgba.len());
+    for (e, a) in expected.rgba.chunks(4).zip(actual.rgba.chunks(4)) {
+        let differs = e
+            .iter()
+            .zip(a.iter())
+            .any(|(&e, &a)| (e as i16 - a as i16).abs() > TOLERANCE as i16);
+        if differs {
+            mismatched += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            diff.extend_from_slice(&[a[0] / 4, a[1] / 4, a[2] / 4, 255]);
+        }
+    }
+    let diff = CapturedFrame {
+        width: actual.width,
+        height: actual.height,
+        rgba: diff,
+    };
+    (mismatched, diff)
+}
+
+fn env_flag(name: &str) -> bool {
+    std::env::var(name).map_or(false, |value| value == "1")
+}
+
+fn check_against_reference(name: &str, actual: &CapturedFrame) {
+    let reference_path = reference_dir().join(format!("{}.png", name));
+    if env_flag("FAE_BLESS") {
+        std::fs::create_dir_all(reference_dir()).unwrap();
+        actual.save_png(&reference_path).unwrap();
+        eprintln!("wrote reference image {}", reference_path.display());
+        return;
+    }
+    if !reference_path.exists() {
+        std::fs::create_dir_all(output_dir()).unwrap();
+        let actual_path = output_dir().join(format!("{}-actual.png", name));
+        actual.save_png(&actual_path).unwrap();
+        panic!(
+            "{}: no reference image {}, check {} and run with FAE_BLESS=1 to add it",
+            name,
+            reference_path.display(),
+            actual_path.display()
+        );
+    }
+    let expected = CapturedFrame::load_png(&reference_path).unwrap();
+    assert_eq!(
+        (expected.width, expected.height),
+        (actual.width, actual.height),
+        "{}: reference image has a different size",
+        name
+    );
+    let (mismatched, diff) = compare(&expected, actual);
+    if mismatched > 0 {
+        std::fs::create_dir_all(output_dir()).unwrap();
+        let actual_path = output_dir().join(format!("{}-actual.png", name));
+        let diff_path = output_dir().join(format!("{}-diff.png", name));
+        actual.save_png(&actual_path).unwrap();
+        diff.save_png(&diff_path).unwrap();
+        panic!(
+            "{}: {} pixels differ from the reference by more than {}, see {} and {}",
+            name,
+            mismatched,
+            TOLERANCE,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+#[test]
+fn sphere() {
+    let mut sphere = Model::sphere(3);
+    sphere.insert_visibly(InstanceData::from_matrix_and_color(
+        nalgebra::Matrix4::new_scaling(0.5),
+        [0.5, 0.0, 0.0],
+    ));
+    if let Some(frame) = render_scene(sphere, &[ViewRect::FULL], None) {
+        check_against_reference("sphere", &frame);
+    }
+}
+
+#[test]
+fn icosahedron() {
+    let mut icosahedron = Model::icosahedron();
+    icosahedron.insert_visibly(InstanceData::from_matrix_and_color(
+        nalgebra::Matrix4::new_scaling(0.5),
+        [0.0, 0.5, 0.0],
+    ));
+    if let Some(frame) = render_scene(icosahedron, &[ViewRect::FULL], None) {
+        check_against_reference("icosahedron", &frame);
+    }
+}
+
+#[test]
+fn sphere_solid() {
+    let mut sphere = Model::sphere(3);
+    sphere.pipeline = SOLID_PIPELINE.to_string();
+    sphere.insert_visibly(InstanceData::from_matrix_and_color(
+        nalgebra::Matrix4::new_scaling(0.5),
+        [0.5, 0.0, 0.0],
+    ));
+    if let Some(frame) = render_scene(sphere, &[ViewRect::FULL], None) {
+        check_against_reference("sphere_solid", &frame);
+    }
+}
+
+#[test]
+fn cube() {
+    let mut cube = Model::cube();
+    cube.insert_visibly(InstanceData::from_matrix_and_color(
+        nalgebra::Matrix4::new_scaling(0.5),
+        [0.0, 0.0, 0.5],
+    ));
+    if let Some(frame) = render_scene(cube, &[ViewRect::FULL], None) {
+        check_against_reference("cube", &frame);
+    }
+}
+
+// the models are uploaded again from their cpu side data, so nothing may change
+#[test]
+fn sphere_after_device_lost() {
+    let mut sphere = Model::sphere(3);
+    sphere.insert_visibly(InstanceData::from_matrix_and_color(
+        nalgebra::Matrix4::new_scaling(0.5),
+        [0.5, 0.0, 0.0],
+    ));
+    if let Some(frame) = render_scene(sphere, &[ViewRect::FULL], Some(FaultPoint::Submit)) {
+        check_against_reference("sphere", &frame);
+    }
+}
+
+// two views side by side and one overlapping them, the later view has to clear its rect
+#[test]
+fn sphere_split_screen() {
+    let mut sphere = Model::sphere(3);
+    sphere.insert_visibly(InstanceData::from_matrix_and_color(
+        nalgebra::Matrix4::new_scaling(0.5),
+        [0.5, 0.0, 0.0],
+    ));
+    let rects = [
+        ViewRect::new(0.0, 0.0, 0.5, 1.0),
+        ViewRect::new(0.5, 0.0, 0.5, 1.0),
+        ViewRect::new(0.35, 0.35, 0.3, 0.3),
+    ];
+    if let Some(frame) = render_scene(sphere, &rects, None) {
+        check_against_reference("sphere_split_screen", &frame);
+    }
+}
+
+// the shipped glsl compiles at runtime and a broken file says where it failed
+#[test]
+fn shader_files_compile() {
+    let shaders = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("shaders");
+    let vertex = load_shader(shaders.join("shader.vert"), ShaderStage::Vertex).unwrap();
+    assert_eq!(vertex.first(), Some(&0x0723_0203), "not spir-v");
+    std::fs::create_dir_all(output_dir()).unwrap();
+    let broken = output_dir().join("broken.frag");
+    std::fs::write(&broken, "#version 450\nvoid main() { oops }\n").unwrap();
+    match load_shader(&broken, ShaderStage::Fragment) {
+        Err(FaeError::Shader { message, .. }) => assert!(message.contains("broken.frag")),
+        other => panic!(
+            "expected a shader error, got {:?}",
+            other.map(|code| code.len())
+        ),
+    }
+}
+
+#[test]
+fn compare_respects_tolerance() {
+    let frame = |value: u8| CapturedFrame {
+        width: 2,
+        height: 1,
+        rgba: vec![value, value, value, 255, 0, 0, 0, 255],
+    };
+    assert_eq!(compare(&frame(100), &frame(100 + TOLERANCE)).0, 0);
+    assert_eq!(compare(&frame(100), &frame(101 + TOLERANCE)).0, 1);
+}
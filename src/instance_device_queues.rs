@@ -1,13 +1,29 @@
+use crate::config::DeviceSelection;
+use crate::debug::DebugConfig;
+use crate::error::{Context, FaeError};
 use crate::surface::FaeSurface;
 use ash::{
     version::{DeviceV1_0, EntryV1_0, InstanceV1_0},
     vk,
 };
 
-pub fn init_instance(
+// instance extensions that are enabled only if the loader has them
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OptionalExtensions {
+    pub debug_utils: bool,
+    // needed to query VK_EXT_memory_budget on a vulkan 1.0 instance
+    pub physical_device_properties2: bool,
+}
+
+// creates the instance with the requested layers and the optional extensions where available.
+// Returns the layers and optional extensions that were actually enabled. The surface
+// extensions are required, creation fails without them.
+pub fn init_instance<'a>(
     entry: &ash::Entry,
-    layer_names: &[&str],
-) -> Result<ash::Instance, ash::InstanceError> {
+    layer_names: &[&'a str],
+    surface_extension_names: &[&std::ffi::CStr],
+    debug: &DebugConfig,
+) -> Result<(ash::Instance, Vec<&'a str>, OptionalExtensions), FaeError> {
     // setup varaibles for ApplicationInfo
     let engine_name = std::ffi::CString::new("GameEngine").unwrap();
     let app_name = std::ffi::CString::new("Rusty VK").unwrap();
@@ -18,8 +34,26 @@ pub fn init_instance(
         .engine_version(vk::make_version(0, 1, 0))
         .api_version(vk::make_version(1, 0, 106));
 
-    // load validation layers and enable DebugUtils extension
-    let layer_names_c: Vec<std::ffi::CString> = layer_names
+    // only enable layers that are installed, validation needs the sdk
+    let available_layers = entry
+        .enumerate_instance_layer_properties()
+        .context("listing the instance layers")?;
+    let mut enabled_layers = vec![];
+    for &layer_name in layer_names {
+        let found = available_layers.iter().any(|l| {
+            let name = unsafe { std::ffi::CStr::from_ptr(l.layer_name.as_ptr()) };
+            name.to_str() == Ok(layer_name)
+        });
+        if found {
+            enabled_layers.push(layer_name);
+        } else {
+            log::warn!(
+                "layer {} is not available, continuing without it",
+                layer_name
+            );
+        }
+    }
+    let layer_names_c: Vec<std::ffi::CString> = enabled_layers
         .iter()
         .map(|&ln| std::ffi::CString::new(ln).unwrap())
         .collect();
@@ -27,69 +61,323 @@ pub fn init_instance(
         .iter()
         .map(|layer_name| layer_name.as_ptr())
         .collect();
-    let extension_name_pointers: Vec<*const i8> = vec![
-        ash::extensions::ext::DebugUtils::name().as_ptr(),
-        ash::extensions::khr::Surface::name().as_ptr(),
-        ash::extensions::khr::Win32Surface::name().as_ptr(),
-    ];
 
-    // setup debug create info
+    // DebugUtils and GetPhysicalDeviceProperties2 are optional
+    let available_extensions = entry
+        .enumerate_instance_extension_properties()
+        .context("listing the instance extensions")?;
+    let available = |wanted: &std::ffi::CStr| {
+        available_extensions.iter().any(|e| {
+            let name = unsafe { std::ffi::CStr::from_ptr(e.extension_name.as_ptr()) };
+            name == wanted
+        })
+    };
+    let debug_utils = available(ash::extensions::ext::DebugUtils::name());
+    let physical_device_properties2 = available(vk::KhrGetPhysicalDeviceProperties2Fn::name());
+    // surface extensions depend on the platform the window was created on
+    let mut extension_name_pointers: Vec<*const i8> = surface_extension_names
+        .iter()
+        .map(|name| name.as_ptr())
+        .collect();
+    if debug_utils {
+        extension_name_pointers.push(ash::extensions::ext::DebugUtils::name().as_ptr());
+    }
+    if physical_device_properties2 {
+        extension_name_pointers.push(vk::KhrGetPhysicalDeviceProperties2Fn::name().as_ptr());
+    }
+
+    // nothing is counted yet, so only the configured messages are delivered
     let mut debug_create_info = vk::DebugUtilsMessengerCreateInfoEXT::builder()
-        .message_severity(
-            vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
-                | vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE
-                | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
-        )
-        .message_type(
-            vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
-                | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE
-                | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
-        )
+        .message_severity(debug.severities)
+        .message_type(debug.types)
         .pfn_user_callback(Some(crate::debug::vulkan_debug_utils_callback));
 
     // setup instance creation info
-    let instance_create_info = vk::InstanceCreateInfo::builder()
-        .push_next(&mut debug_create_info)
+    let mut instance_create_info = vk::InstanceCreateInfo::builder()
         .application_info(&app_info)
         .enabled_layer_names(&layer_name_pointers)
         .enabled_extension_names(&extension_name_pointers);
+    // also report problems during instance creation and destruction, vulkan does not accept
+    // an empty filter
+    if debug_utils && !debug.severities.is_empty() && !debug.types.is_empty() {
+        instance_create_info = instance_create_info.push_next(&mut debug_create_info);
+    }
+
+    let instance =
+        unsafe { entry.create_instance(&instance_create_info, None) }.map_err(|e| match e {
+            ash::InstanceError::VkError(result) => FaeError::Vulkan {
+                step: "creating the instance",
+                result,
+            },
+            ash::InstanceError::LoadError(_) => FaeError::Vulkan {
+                step: "loading the instance functions",
+                result: vk::Result::ERROR_INITIALIZATION_FAILED,
+            },
+        })?;
+    let optional_extensions = OptionalExtensions {
+        debug_utils,
+        physical_device_properties2,
+    };
+    Ok((instance, enabled_layers, optional_extensions))
+}
+
+pub fn supports_device_extension(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    wanted: &std::ffi::CStr,
+) -> Result<bool, vk::Result> {
+    let available_extensions =
+        unsafe { instance.enumerate_device_extension_properties(physical_device)? };
+    Ok(available_extensions.iter().any(|e| {
+        let name = unsafe { std::ffi::CStr::from_ptr(e.extension_name.as_ptr()) };
+        name == wanted
+    }))
+}
+
+// a physical device and why it can or can not be used, see list_physical_devices
+#[derive(Debug, Clone)]
+pub struct DeviceCandidate {
+    pub index: usize,
+    pub name: String,
+    pub device_type: vk::PhysicalDeviceType,
+    // None if the device can not be used
+    pub score: Option<u32>,
+    pub reasons: Vec<String>,
+}
 
-    unsafe { entry.create_instance(&instance_create_info, None) }
+impl DeviceCandidate {
+    pub fn is_suitable(&self) -> bool {
+        self.score.is_some()
+    }
+}
+
+impl std::fmt::Display for DeviceCandidate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.score {
+            Some(score) => write!(
+                f,
+                "[{}] {} ({:?}): accepted with score {}",
+                self.index, self.name, self.device_type, score
+            )?,
+            None => write!(
+                f,
+                "[{}] {} ({:?}): rejected",
+                self.index, self.name, self.device_type
+            )?,
+        }
+        for reason in &self.reasons {
+            write!(f, "\n    {}", reason)?;
+        }
+        Ok(())
+    }
 }
 
+// scores every physical device, rejecting those without the queues, extensions, features
+// or limits the renderer needs
+pub fn list_physical_devices(
+    instance: &ash::Instance,
+    surfaces: Option<&FaeSurface>,
+    device_extension_names: &[&std::ffi::CStr],
+) -> Result<Vec<DeviceCandidate>, vk::Result> {
+    let phys_devs = unsafe { instance.enumerate_physical_devices()? };
+    let mut candidates = Vec::with_capacity(phys_devs.len());
+    for (index, p) in phys_devs.into_iter().enumerate() {
+        candidates.push(rate_physical_device(
+            instance,
+            p,
+            index,
+            surfaces,
+            device_extension_names,
+        )?);
+    }
+    Ok(candidates)
+}
+
+fn rate_physical_device(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    index: usize,
+    surfaces: Option<&FaeSurface>,
+    device_extension_names: &[&std::ffi::CStr],
+) -> Result<DeviceCandidate, vk::Result> {
+    let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+    let features = unsafe { instance.get_physical_device_features(physical_device) };
+    let queue_families = QueueFamilies::init(instance, physical_device, surfaces)?;
+    let available_extensions =
+        unsafe { instance.enumerate_device_extension_properties(physical_device)? };
+    let missing_extensions = device_extension_names
+        .iter()
+        .filter(|required| {
+            !available_extensions.iter().any(|e| {
+                let name = unsafe { std::ffi::CStr::from_ptr(e.extension_name.as_ptr()) };
+                name == **required
+            })
+        })
+        .map(|required| required.to_string_lossy().into_owned())
+        .collect();
+    Ok(score_device(
+        index,
+        &properties,
+        &features,
+        queue_families,
+        surfaces.is_some(),
+        missing_extensions,
+    ))
+}
+
+// the rating itself, from what rate_physical_device queried
+fn score_device(
+    index: usize,
+    properties: &vk::PhysicalDeviceProperties,
+    features: &vk::PhysicalDeviceFeatures,
+    queue_families: Option<QueueFamilies>,
+    presenting: bool,
+    missing_extensions: Vec<String>,
+) -> DeviceCandidate {
+    let name = unsafe { std::ffi::CStr::from_ptr(properties.device_name.as_ptr()) }
+        .to_string_lossy()
+        .into_owned();
+    let mut reasons = vec![];
+    let mut suitable = true;
+
+    // prefer real gpus, but software implementations like lavapipe still work
+    let mut score = match properties.device_type {
+        vk::PhysicalDeviceType::DISCRETE_GPU => 1000,
+        vk::PhysicalDeviceType::INTEGRATED_GPU => 500,
+        vk::PhysicalDeviceType::VIRTUAL_GPU => 200,
+        vk::PhysicalDeviceType::CPU => 100,
+        _ => 50,
+    };
+    reasons.push(format!("{:?} (+{})", properties.device_type, score));
+
+    match queue_families {
+        None => {
+            suitable = false;
+            reasons.push(if presenting {
+                "no graphics queue family that can present to the surface".to_string()
+            } else {
+                "no graphics queue family".to_string()
+            });
+        }
+        Some(queue_families) if queue_families.has_dedicated_transfer() => {
+            score += 50;
+            reasons.push("dedicated transfer queue family (+50)".to_string());
+        }
+        Some(_) => {}
+    }
+
+    for missing in missing_extensions {
+        suitable = false;
+        reasons.push(format!("missing extension {}", missing));
+    }
+
+    // the pipeline draws in wireframe
+    if features.fill_mode_non_solid == vk::FALSE {
+        suitable = false;
+        reasons.push("missing feature fill_mode_non_solid".to_string());
+    }
+
+    // one set for the camera, vertex and instance data use 11 attribute locations
+    let limits = &properties.limits;
+    if limits.max_bound_descriptor_sets < 1 || limits.max_vertex_input_attributes < 11 {
+        suitable = false;
+        reasons.push("limits too low for the default pipeline".to_string());
+    }
+    // larger render targets as a tie breaker between similar devices
+    let size_bonus = limits.max_image_dimension2_d / 1024;
+    score += size_bonus;
+    reasons.push(format!(
+        "max 2d image size {} (+{})",
+        limits.max_image_dimension2_d, size_bonus
+    ));
+
+    DeviceCandidate {
+        index,
+        name,
+        device_type: properties.device_type,
+        score: if suitable { Some(score) } else { None },
+        reasons,
+    }
+}
+
+// the best suitable device for Auto, otherwise the first suitable one that matches
+fn choose_candidate<'a>(
+    candidates: &'a [DeviceCandidate],
+    selection: &DeviceSelection,
+) -> Option<&'a DeviceCandidate> {
+    match selection {
+        DeviceSelection::Auto => candidates
+            .iter()
+            .filter_map(|c| c.score.map(|score| (score, c)))
+            .max_by_key(|(score, _)| *score)
+            .map(|(_, c)| c),
+        DeviceSelection::Index(index) => candidates
+            .iter()
+            .find(|c| c.index == *index && c.is_suitable()),
+        DeviceSelection::Name(name) => {
+            let name = name.to_lowercase();
+            candidates
+                .iter()
+                .find(|c| c.name.to_lowercase().contains(&name) && c.is_suitable())
+        }
+    }
+}
+
+// picks the physical device to render with. The FAE_DEVICE environment variable overrides the
+// selection from the config, it holds either a device index or part of a device name.
 pub fn init_physical_device_and_properties(
     instance: &ash::Instance,
+    surfaces: Option<&FaeSurface>,
+    device_extension_names: &[&std::ffi::CStr],
+    selection: &DeviceSelection,
 ) -> Result<
     (
         vk::PhysicalDevice,
         vk::PhysicalDeviceProperties,
         vk::PhysicalDeviceFeatures,
+        QueueFamilies,
     ),
-    vk::Result,
+    FaeError,
 > {
-    // pick gpu to use (in this case it the discrete gpu)
-    let phys_devs = unsafe { instance.enumerate_physical_devices()? };
-    let mut chosen = None;
-    for p in phys_devs {
-        let properties = unsafe { instance.get_physical_device_properties(p) };
-        let features = unsafe { instance.get_physical_device_features(p) };
-        if properties.device_type == vk::PhysicalDeviceType::DISCRETE_GPU {
-            chosen = Some((p, properties, features));
-        }
-    }
-    Ok(chosen.unwrap())
+    let selection = match std::env::var("FAE_DEVICE") {
+        Ok(value) => DeviceSelection::parse(&value),
+        Err(_) => selection.clone(),
+    };
+    let candidates = list_physical_devices(instance, surfaces, device_extension_names)
+        .context("rating the physical devices")?;
+    let chosen = choose_candidate(&candidates, &selection);
+    let chosen_index = match chosen {
+        Some(chosen) => chosen.index,
+        None => return Err(FaeError::NoSuitableDevice(candidates)),
+    };
+    let physical_device = unsafe { instance.enumerate_physical_devices() }
+        .context("listing the physical devices")?[chosen_index];
+    let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+    let features = unsafe { instance.get_physical_device_features(physical_device) };
+    // a suitable device always has them, looked up again since the rating only kept the reasons
+    let queue_families = match QueueFamilies::init(instance, physical_device, surfaces)
+        .context("finding the queue families")?
+    {
+        Some(queue_families) => queue_families,
+        None => return Err(FaeError::NoSuitableDevice(candidates)),
+    };
+    Ok((physical_device, properties, features, queue_families))
 }
 
+// the queue families of the chosen device, transfer is the same as graphics if there is no
+// dedicated family for it
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct QueueFamilies {
-    pub graphics_q_index: Option<u32>,
-    pub transfer_q_index: Option<u32>,
+    pub graphics_q_index: u32,
+    pub transfer_q_index: u32,
 }
 impl QueueFamilies {
+    // None if the device has no graphics family that can present to the surface
     pub fn init(
         instance: &ash::Instance,
         physical_device: vk::PhysicalDevice,
-        surfaces: &FaeSurface,
-    ) -> Result<QueueFamilies, vk::Result> {
+        surfaces: Option<&FaeSurface>,
+    ) -> Result<Option<QueueFamilies>, vk::Result> {
         // TODO: this could be done like in the ash examples to make sure
         // physical device is surface and graphics capable
         // find queue family indices
@@ -98,24 +386,43 @@ impl QueueFamilies {
         let mut found_graphics_q_index = None;
         let mut found_transfer_q_index = None;
         for (index, q_fam) in queue_family_properties.iter().enumerate() {
+            // without a surface (headless) there is nothing to present to
+            let can_present = match surfaces {
+                Some(surfaces) => {
+                    surfaces.get_physical_device_surface_support(physical_device, index)?
+                }
+                None => true,
+            };
             if q_fam.queue_count > 0
                 && q_fam.queue_flags.contains(vk::QueueFlags::GRAPHICS)
-                && surfaces.get_physical_device_surface_support(physical_device, index)?
+                && can_present
             {
                 found_graphics_q_index = Some(index as u32);
             }
-            if q_fam.queue_count > 0 && q_fam.queue_flags.contains(vk::QueueFlags::TRANSFER) {
-                if found_transfer_q_index.is_none()
-                    || !q_fam.queue_flags.contains(vk::QueueFlags::GRAPHICS)
-                {
-                    found_transfer_q_index = Some(index as u32);
-                }
+            // graphics and compute families can always transfer, even without reporting it
+            let can_transfer = q_fam.queue_flags.intersects(
+                vk::QueueFlags::TRANSFER | vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE,
+            );
+            // prefer a family without graphics, so uploads can run next to rendering
+            if q_fam.queue_count > 0
+                && can_transfer
+                && !q_fam.queue_flags.contains(vk::QueueFlags::GRAPHICS)
+                && found_transfer_q_index.is_none()
+            {
+                found_transfer_q_index = Some(index as u32);
             }
         }
-        Ok(QueueFamilies {
-            graphics_q_index: found_graphics_q_index,
-            transfer_q_index: found_transfer_q_index,
-        })
+        Ok(
+            found_graphics_q_index.map(|graphics_q_index| QueueFamilies {
+                graphics_q_index,
+                // otherwise uploads go through the graphics queue
+                transfer_q_index: found_transfer_q_index.unwrap_or(graphics_q_index),
+            }),
+        )
+    }
+
+    pub fn has_dedicated_transfer(&self) -> bool {
+        self.transfer_q_index != self.graphics_q_index
     }
 }
 
@@ -129,6 +436,8 @@ pub fn init_device_and_queues(
     physical_device: vk::PhysicalDevice,
     queue_families: &QueueFamilies,
     layer_names: &[&str],
+    device_extension_names: &[&std::ffi::CStr],
+    features: &vk::PhysicalDeviceFeatures,
 ) -> Result<(ash::Device, Queues), vk::Result> {
     // setup validation layers again it's a compromise
     // TODO: try to think of a better way to do this
@@ -143,35 +452,37 @@ pub fn init_device_and_queues(
 
     // create a logical device as primary interface to gpu
     let priorities = [1.0f32];
-    let queue_infos = [
-        // GRAPHICS QUEUE
-        // TODO: use only one DeviceQueueCreateInfo
-        vk::DeviceQueueCreateInfo::builder()
-            .queue_family_index(queue_families.graphics_q_index.unwrap())
-            .queue_priorities(&priorities)
-            .build(),
-        // TRANSFER QUEUE
-        vk::DeviceQueueCreateInfo::builder()
-            .queue_family_index(queue_families.transfer_q_index.unwrap())
-            .queue_priorities(&priorities)
-            .build(),
-    ];
-
-    let features = vk::PhysicalDeviceFeatures::builder().fill_mode_non_solid(true);
+    // GRAPHICS QUEUE
+    let mut queue_infos = vec![vk::DeviceQueueCreateInfo::builder()
+        .queue_family_index(queue_families.graphics_q_index)
+        .queue_priorities(&priorities)
+        .build()];
+    // TRANSFER QUEUE, shares the graphics queue if there is no separate family for it
+    if queue_families.has_dedicated_transfer() {
+        queue_infos.push(
+            vk::DeviceQueueCreateInfo::builder()
+                .queue_family_index(queue_families.transfer_q_index)
+                .queue_priorities(&priorities)
+                .build(),
+        );
+    }
+
     // device creation
-    let device_extension_name_pointers: Vec<*const i8> =
-        vec![ash::extensions::khr::Swapchain::name().as_ptr()];
+    let device_extension_name_pointers: Vec<*const i8> = device_extension_names
+        .iter()
+        .map(|name| name.as_ptr())
+        .collect();
     let device_create_info = vk::DeviceCreateInfo::builder()
         .queue_create_infos(&queue_infos)
         .enabled_extension_names(&device_extension_name_pointers)
         .enabled_layer_names(&layer_name_pointers)
-        .enabled_features(&features);
+        .enabled_features(features);
     let logical_device =
         unsafe { instance.create_device(physical_device, &device_create_info, None)? };
     let graphics_queue =
-        unsafe { logical_device.get_device_queue(queue_families.graphics_q_index.unwrap(), 0) };
+        unsafe { logical_device.get_device_queue(queue_families.graphics_q_index, 0) };
     let transfer_queue =
-        unsafe { logical_device.get_device_queue(queue_families.transfer_q_index.unwrap(), 0) };
+        unsafe { logical_device.get_device_queue(queue_families.transfer_q_index, 0) };
     Ok((
         logical_device,
         Queues {
@@ -180,3 +491,99 @@ pub fn init_device_and_queues(
         },
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties(device_type: vk::PhysicalDeviceType, name: &str) -> vk::PhysicalDeviceProperties {
+        let mut properties = vk::PhysicalDeviceProperties {
+            device_type,
+            limits: vk::PhysicalDeviceLimits {
+                max_bound_descriptor_sets: 4,
+                max_vertex_input_attributes: 16,
+                max_image_dimension2_d: 16384,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        for (target, byte) in properties.device_name.iter_mut().zip(name.bytes()) {
+            *target = byte as std::os::raw::c_char;
+        }
+        properties
+    }
+
+    fn features() -> vk::PhysicalDeviceFeatures {
+        vk::PhysicalDeviceFeatures {
+            fill_mode_non_solid: vk::TRUE,
+            ..Default::default()
+        }
+    }
+
+    const SHARED: QueueFamilies = QueueFamilies {
+        graphics_q_index: 0,
+        transfer_q_index: 0,
+    };
+
+    #[test]
+    fn scores_device_type_transfer_and_size() {
+        let discrete = properties(vk::PhysicalDeviceType::DISCRETE_GPU, "discrete");
+        let candidate = score_device(0, &discrete, &features(), Some(SHARED), true, vec![]);
+        assert_eq!(candidate.name, "discrete");
+        assert_eq!(candidate.score, Some(1000 + 16));
+        let dedicated = QueueFamilies {
+            graphics_q_index: 0,
+            transfer_q_index: 1,
+        };
+        let candidate = score_device(0, &discrete, &features(), Some(dedicated), true, vec![]);
+        assert_eq!(candidate.score, Some(1000 + 50 + 16));
+        let cpu = properties(vk::PhysicalDeviceType::CPU, "llvmpipe");
+        let candidate = score_device(1, &cpu, &features(), Some(SHARED), true, vec![]);
+        assert_eq!(candidate.score, Some(100 + 16));
+    }
+
+    #[test]
+    fn rejects_unsuitable_devices() {
+        let discrete = properties(vk::PhysicalDeviceType::DISCRETE_GPU, "discrete");
+        assert!(!score_device(0, &discrete, &features(), None, true, vec![]).is_suitable());
+        let missing = vec!["VK_KHR_swapchain".to_string()];
+        let candidate = score_device(0, &discrete, &features(), Some(SHARED), true, missing);
+        assert!(!candidate.is_suitable());
+        assert!(candidate
+            .reasons
+            .contains(&"missing extension VK_KHR_swapchain".to_string()));
+        let no_wireframe = vk::PhysicalDeviceFeatures::default();
+        assert!(
+            !score_device(0, &discrete, &no_wireframe, Some(SHARED), true, vec![]).is_suitable()
+        );
+        let mut small = discrete;
+        small.limits.max_vertex_input_attributes = 8;
+        assert!(!score_device(0, &small, &features(), Some(SHARED), true, vec![]).is_suitable());
+    }
+
+    #[test]
+    fn chooses_by_selection() {
+        let candidate = |index: usize, name: &str, score: Option<u32>| DeviceCandidate {
+            index,
+            name: name.to_string(),
+            device_type: vk::PhysicalDeviceType::OTHER,
+            score,
+            reasons: vec![],
+        };
+        let candidates = [
+            candidate(0, "Intel UHD", Some(516)),
+            candidate(1, "NVIDIA GeForce", Some(1016)),
+            candidate(2, "llvmpipe", None),
+        ];
+        let chosen =
+            |selection: DeviceSelection| choose_candidate(&candidates, &selection).map(|c| c.index);
+        assert_eq!(chosen(DeviceSelection::Auto), Some(1));
+        assert_eq!(chosen(DeviceSelection::Index(0)), Some(0));
+        assert_eq!(chosen(DeviceSelection::Name("intel".to_string())), Some(0));
+        // unsuitable or unknown devices are never chosen
+        assert_eq!(chosen(DeviceSelection::Index(2)), None);
+        assert_eq!(chosen(DeviceSelection::Index(3)), None);
+        assert_eq!(chosen(DeviceSelection::Name("LLVMPIPE".to_string())), None);
+        assert!(choose_candidate(&[], &DeviceSelection::Auto).is_none());
+    }
+}
@@ -0,0 +1,64 @@
+pub mod buffer;
+pub mod camera;
+pub mod capture;
+pub mod config;
+pub mod debug;
+pub mod error;
+pub mod fae;
+pub mod frames;
+#[cfg(test)]
+mod golden;
+pub mod instance_device_queues;
+pub mod memory;
+pub mod memory_report;
+pub mod model;
+pub mod offscreen;
+pub mod pools_and_command_buffers;
+pub mod profiler;
+pub mod queries;
+pub mod render_pass_and_pipeline;
+pub mod resources;
+pub mod shaders;
+pub mod surface;
+pub mod swapchain;
+pub mod upload;
+pub mod vertex_layout;
+use ash::{
+    version::{DeviceV1_0, EntryV1_0, InstanceV1_0},
+    vk,
+};
+pub use buffer::Buffer;
+pub use camera::{Camera, View, ViewRect};
+pub use capture::CapturedFrame;
+pub use config::{DeviceSelection, FaeConfig, SwapchainConfig};
+pub use debug::DebugConfig;
+use debug::FaeDebug;
+use error::Context;
+pub use error::FaeError;
+pub use fae::{Fae, FaultPoint, FrameResult};
+use frames::FrameSync;
+use instance_device_queues::{
+    init_device_and_queues, init_instance, init_physical_device_and_properties,
+    supports_device_extension, QueueFamilies, Queues,
+};
+pub use instance_device_queues::{list_physical_devices, DeviceCandidate};
+use memory::{LinearAllocator, TransientSlice};
+pub use memory_report::{CategoryStats, HeapBudget, HeapStats, MemoryReport, MemoryTypeStats};
+pub use model::{InstanceData, Model, VertexData};
+use offscreen::FaeOffscreen;
+use pools_and_command_buffers::{create_command_buffers, Pools};
+pub use profiler::FaeProfiler;
+use queries::FaeQueries;
+pub use queries::{FrameStatistics, ModelStatistics, PipelineStatistics};
+use render_pass_and_pipeline::init_render_pass;
+pub use render_pass_and_pipeline::{
+    BlendMode, PipelineBuilder, Pipelines, SOLID_PIPELINE, WIREFRAME_PIPELINE,
+};
+pub use resources::{FaeResources, Image, MemoryCategory};
+use shaders::ShaderWatcher;
+pub use shaders::{load_shader, ShaderStage};
+use std::rc::Rc;
+use surface::FaeSurface;
+use swapchain::FaeSwapchain;
+pub use upload::FaeUploader;
+pub use vertex_layout::{VertexAttribute, VertexFormat, VertexInput, VertexLayout};
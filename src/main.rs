@@ -1,45 +1,73 @@
-mod buffer;
-mod camera;
-mod debug;
-mod fae;
-mod instance_device_queues;
-mod model;
-mod pools_and_command_buffers;
-mod render_pass_and_pipeline;
-mod surface;
-mod swapchain;
-use ash::{
-    version::{DeviceV1_0, InstanceV1_0},
-    vk,
+use vulkan_renderer::{
+    Camera, Fae, FaeConfig, FaultPoint, FrameResult, InstanceData, Model, Pipelines, View,
+    ViewRect, SOLID_PIPELINE, WIREFRAME_PIPELINE,
 };
-use buffer::Buffer;
-use debug::FaeDebug;
-use fae::Fae;
-use instance_device_queues::{
-    init_device_and_queues, init_instance, init_physical_device_and_properties, QueueFamilies,
-    Queues,
-};
-use model::{InstanceData, Model};
-use pools_and_command_buffers::{create_command_buffers, Pools};
-use render_pass_and_pipeline::{init_render_pass, Pipeline};
-use surface::FaeSurface;
-use swapchain::FaeSwapchain;
+
+// where the main camera and the overview camera render, V cycles through them
+fn layout_rects(layout: usize) -> Vec<ViewRect> {
+    match layout % 3 {
+        0 => vec![ViewRect::FULL],
+        // split screen
+        1 => vec![
+            ViewRect::new(0.0, 0.0, 0.5, 1.0),
+            ViewRect::new(0.5, 0.0, 0.5, 1.0),
+        ],
+        // picture in picture in the top right corner
+        _ => vec![ViewRect::FULL, ViewRect::new(0.7, 0.05, 0.25, 0.25)],
+    }
+}
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    use camera::Camera;
+    // validation messages are logged with the target "vulkan", see RUST_LOG
+    env_logger::init();
     let event_loop = winit::event_loop::EventLoop::new();
     let window = winit::window::Window::new(&event_loop)?;
-    let mut fae = Fae::init(window)?;
+    let config = FaeConfig {
+        profiling: true,
+        statistics: true,
+        ..FaeConfig::default()
+    };
+    let mut fae = Fae::init_with_config(window, &config)?;
+    // per frame gpu timings, P prints the averages and O the last frame's statistics
+    if let (Some(path), Some(profiler)) = (std::env::var_os("FAE_PROFILE_CSV"), fae.profiler_mut())
+    {
+        profiler.enable_csv(path)?;
+    }
+    println!("rendering on {}", fae.device_name());
+    if let (Some(format), Some(present_mode)) = (fae.surface_format(), fae.present_mode()) {
+        println!(
+            "swapchain uses {:?} in {:?}, presenting with {:?}",
+            format.format, format.color_space, present_mode
+        );
+    }
+    // the shaders in shaders/ are compiled at runtime and rebuilt when they are saved, the
+    // built in copies stay in use when started from another directory
+    for &(name, polygon_mode) in &[
+        (SOLID_PIPELINE, ash::vk::PolygonMode::FILL),
+        (WIREFRAME_PIPELINE, ash::vk::PolygonMode::LINE),
+    ] {
+        let builder = Pipelines::builder()
+            .polygon_mode(polygon_mode)
+            .shader_files("shaders/shader.vert", "shaders/shader.frag");
+        if let Err(e) = fae.add_pipeline(name, builder) {
+            eprintln!("using the built in shaders: {}", e);
+        }
+    }
     let mut camera = Camera::builder().build();
+    let overview = Camera::builder()
+        .position(nalgebra::Vector3::new(0.0, -3.0, -3.0))
+        .view_direction(nalgebra::Vector3::new(0.0, 1.0, 1.0))
+        .build();
+    let mut layout = 0;
     let mut sphere = Model::sphere(3);
     sphere.insert_visibly(InstanceData::from_matrix_and_color(
         nalgebra::Matrix4::new_scaling(0.5),
         [0.5, 0.0, 0.0],
     ));
-    sphere.update_vertex_buffer(&fae.allocator)?;
-    sphere.update_index_buffer(&fae.allocator)?;
-    sphere.update_instance_buffer(&fae.allocator)?;
+    sphere.update_vertex_buffer(&fae.uploader)?;
+    sphere.update_index_buffer(&fae.uploader)?;
     fae.models = vec![sphere];
+    let mut swapchain_out_of_date = false;
     use winit::event::{Event, WindowEvent};
     event_loop.run(move |event, _, controlflow| match event {
         Event::WindowEvent {
@@ -48,6 +76,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         } => {
             *controlflow = winit::event_loop::ControlFlow::Exit;
         }
+        Event::WindowEvent {
+            event: WindowEvent::Resized(_),
+            ..
+        } => {
+            swapchain_out_of_date = true;
+        }
         Event::WindowEvent {
             event: WindowEvent::KeyboardInput { input, .. },
             ..
@@ -83,82 +117,115 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     winit::event::VirtualKeyCode::Down => {
                         camera.turn_down(0.02);
                     }
+                    // switch between wireframe and filled triangles
+                    winit::event::VirtualKeyCode::F => {
+                        for m in &mut fae.models {
+                            m.pipeline = if m.pipeline == WIREFRAME_PIPELINE {
+                                SOLID_PIPELINE.to_string()
+                            } else {
+                                WIREFRAME_PIPELINE.to_string()
+                            };
+                        }
+                    }
+                    winit::event::VirtualKeyCode::F12 => {
+                        if let Err(e) = fae.request_capture() {
+                            eprintln!("{}", e);
+                        }
+                    }
+                    winit::event::VirtualKeyCode::O => {
+                        if let Some(statistics) = fae.frame_statistics() {
+                            for (i, model) in statistics.models.iter().enumerate() {
+                                println!("model {}: {:?}", i, model);
+                            }
+                            println!("total: {:?}", statistics.total);
+                        }
+                    }
+                    winit::event::VirtualKeyCode::P => {
+                        if let Some(profiler) = fae.profiler() {
+                            for (region, milliseconds) in profiler.averages() {
+                                println!("{}: {:.3} ms", region, milliseconds);
+                            }
+                        }
+                    }
+                    winit::event::VirtualKeyCode::V => {
+                        layout += 1;
+                    }
+                    // pretend the driver was reset to try the recovery
+                    winit::event::VirtualKeyCode::L => {
+                        fae.inject_device_lost(FaultPoint::Submit);
+                    }
+                    winit::event::VirtualKeyCode::M => match fae.memory_report() {
+                        Ok(report) => {
+                            println!("{}", report);
+                            if let Ok(path) = std::env::var("FAE_MEMORY_JSON") {
+                                if let Err(e) = report.write_json(&path) {
+                                    eprintln!("could not write {}: {}", path, e);
+                                }
+                            }
+                        }
+                        Err(e) => eprintln!("memory report failed: {}", e),
+                    },
                     _ => {}
                 }
             }
         }
         Event::MainEventsCleared => {
-            fae.window.request_redraw();
+            // sleep until the next window event instead of spinning while minimised
+            if fae.is_minimised() {
+                *controlflow = winit::event_loop::ControlFlow::Wait;
+            } else if let Some(window) = &fae.window {
+                *controlflow = winit::event_loop::ControlFlow::Poll;
+                window.request_redraw();
+            }
         }
         Event::RedrawRequested(_) => {
-            // aquire the next image
-            let (image_index, _) = unsafe {
-                fae.swapchain
-                    .swapchain_loader
-                    .acquire_next_image(
-                        fae.swapchain.swapchain,
-                        std::u64::MAX,
-                        fae.swapchain.image_available[fae.swapchain.current_image],
-                        vk::Fence::null(),
-                    )
-                    .expect("image aquisition trouble")
-            };
-            unsafe {
-                // wait for fence
-                fae.device
-                    .wait_for_fences(
-                        &[fae.swapchain.may_begin_drawing[fae.swapchain.current_image]],
-                        true,
-                        std::u64::MAX,
-                    )
-                    .expect("fence-waiting");
-                // reset fence
-                fae.device
-                    .reset_fences(&[fae.swapchain.may_begin_drawing[fae.swapchain.current_image]])
-                    .expect("resetting fences");
-            };
-            for m in &mut fae.models {
-                camera.update_buffer(&fae.allocator, &mut fae.uniform_buffer);
-                m.update_instance_buffer(&fae.allocator).unwrap();
+            // nothing to draw into while minimised, wait for the next resize
+            if fae.is_minimised() {
+                return;
+            }
+            if swapchain_out_of_date {
+                if let Err(e) = fae.recreate_swapchain() {
+                    log::error!("{}", e);
+                    *controlflow = winit::event_loop::ControlFlow::Exit;
+                    return;
+                }
+                swapchain_out_of_date = false;
+            }
+            if let Err(e) = fae.reload_shaders() {
+                eprintln!("{}", e);
+            }
+            // draw_views gives every camera the aspect of its rectangle
+            let rects = layout_rects(layout);
+            let views: Vec<View> = rects
+                .iter()
+                .zip(&[&camera, &overview])
+                .map(|(&rect, &camera)| View { camera, rect })
+                .collect();
+            match fae.draw_views(&views) {
+                Ok(FrameResult::Success) => {}
+                Ok(FrameResult::Suboptimal) | Ok(FrameResult::OutOfDate) => {
+                    swapchain_out_of_date = true
+                }
+                Ok(FrameResult::DeviceRecovered) => {
+                    println!("the device was lost and has been rebuilt");
+                }
+                Err(e) => {
+                    log::error!("{}", e);
+                    *controlflow = winit::event_loop::ControlFlow::Exit;
+                    return;
+                }
+            }
+            if let Some(frame) = fae.take_capture() {
+                let timestamp = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                let path = format!("screenshot-{}.png", timestamp);
+                match frame.save_png(&path) {
+                    Ok(()) => println!("saved screenshot to {}", path),
+                    Err(e) => println!("failed to save screenshot: {}", e),
+                }
             }
-            //update command buffer
-            fae.update_command_buffer(image_index as usize)
-                .expect("updateing the command buffer");
-            // command buffer setup info
-            let semaphores_available = [fae.swapchain.image_available[fae.swapchain.current_image]];
-            let waiting_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
-            let semaphores_finished =
-                [fae.swapchain.rendering_finished[fae.swapchain.current_image]];
-            let command_buffers = [fae.command_buffers[image_index as usize]];
-            let submit_info = [vk::SubmitInfo::builder()
-                .wait_semaphores(&semaphores_available)
-                .wait_dst_stage_mask(&waiting_stages)
-                .command_buffers(&command_buffers)
-                .signal_semaphores(&semaphores_finished)
-                .build()];
-            // submit command buffer
-            unsafe {
-                fae.device
-                    .queue_submit(
-                        fae.queues.graphics_queue,
-                        &submit_info,
-                        fae.swapchain.may_begin_drawing[fae.swapchain.current_image],
-                    )
-                    .expect("queue submission");
-            };
-            // present image to screen
-            let swapchains = [fae.swapchain.swapchain];
-            let indices = [image_index];
-            let present_info = vk::PresentInfoKHR::builder()
-                .wait_semaphores(&semaphores_finished)
-                .swapchains(&swapchains)
-                .image_indices(&indices);
-            unsafe {
-                fae.swapchain
-                    .swapchain_loader
-                    .queue_present(fae.queues.graphics_queue, &present_info)
-                    .expect("queue presentation");
-            };
         }
         _ => {}
     });
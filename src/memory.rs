@@ -0,0 +1,155 @@
+use crate::buffer::Buffer;
+use crate::error::FaeError;
+use crate::resources::{FaeResources, MemoryCategory};
+use ash::vk;
+use std::rc::Rc;
+
+// part of a LinearAllocator's buffer holding data for one frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransientSlice {
+    pub buffer: vk::Buffer,
+    pub offset: u64,
+    pub size: u64,
+}
+
+// hands out slices of one persistently mapped buffer for data that is rewritten every frame,
+// like the camera uniforms and instance streams. Each frame in flight has one for uniforms and
+// one for instances, reset after that frame's fence was waited on. A full buffer is replaced by
+// one twice the size, the old one is only released since slices of it may already be recorded.
+pub struct LinearAllocator {
+    buffer: Buffer,
+    cursor: Cursor,
+    name: String,
+    category: MemoryCategory,
+    usage: vk::BufferUsageFlags,
+    resources: Rc<FaeResources>,
+}
+
+impl LinearAllocator {
+    pub fn new(
+        resources: &Rc<FaeResources>,
+        name: &str,
+        category: MemoryCategory,
+        size_in_bytes: u64,
+        usage: vk::BufferUsageFlags,
+    ) -> Result<LinearAllocator, FaeError> {
+        let buffer = Buffer::with_category(
+            resources,
+            name,
+            category,
+            size_in_bytes,
+            usage,
+            vk_mem::MemoryUsage::CpuToGpu,
+        )?;
+        Ok(LinearAllocator {
+            buffer,
+            cursor: Cursor::default(),
+            name: name.to_string(),
+            category,
+            usage,
+            resources: resources.clone(),
+        })
+    }
+
+    pub fn reset(&mut self) {
+        self.cursor.reset();
+    }
+
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer.buffer
+    }
+
+    // copies data into the buffer at the next offset that is a multiple of alignment
+    pub fn push<T: Sized>(
+        &mut self,
+        data: &[T],
+        alignment: u64,
+    ) -> Result<TransientSlice, FaeError> {
+        let size = (data.len() * std::mem::size_of::<T>()) as u64;
+        let offset = match self
+            .cursor
+            .place(size, alignment, self.buffer.size_in_bytes())
+        {
+            Some(offset) => offset,
+            None => {
+                let new_size = (self.buffer.size_in_bytes() * 2).max(size);
+                self.buffer = Buffer::with_category(
+                    &self.resources,
+                    &self.name,
+                    self.category,
+                    new_size,
+                    self.usage,
+                    vk_mem::MemoryUsage::CpuToGpu,
+                )?;
+                // the new buffer is empty, the data goes at its start
+                self.cursor = Cursor { offset: size };
+                0
+            }
+        };
+        self.buffer.write_at(offset, data);
+        Ok(TransientSlice {
+            buffer: self.buffer.buffer,
+            offset,
+            size,
+        })
+    }
+}
+
+// where the next push goes, kept apart from the buffer so the placement needs no device
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Cursor {
+    offset: u64,
+}
+
+impl Cursor {
+    fn reset(&mut self) {
+        self.offset = 0;
+    }
+
+    // start of size bytes at the next multiple of alignment, None if they do not fit into
+    // capacity. The cursor only moves past them if they fit.
+    fn place(&mut self, size: u64, alignment: u64, capacity: u64) -> Option<u64> {
+        let alignment = alignment.max(1);
+        let offset = (self.offset + alignment - 1) / alignment * alignment;
+        if offset + size > capacity {
+            return None;
+        }
+        self.offset = offset + size;
+        Some(offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn places_at_aligned_offsets() {
+        let mut cursor = Cursor::default();
+        assert_eq!(cursor.place(128, 256, 1024), Some(0));
+        assert_eq!(cursor.place(128, 256, 1024), Some(256));
+        // instances only need the alignment of their type, 0 means none
+        assert_eq!(cursor.place(12, 4, 1024), Some(384));
+        assert_eq!(cursor.place(1, 0, 1024), Some(396));
+        assert_eq!(cursor.place(4, 4, 1024), Some(400));
+    }
+
+    #[test]
+    fn full_cursor_stays_in_place() {
+        let mut cursor = Cursor::default();
+        assert_eq!(cursor.place(100, 1, 256), Some(0));
+        // the padding up to 256 would already fill the buffer
+        assert_eq!(cursor.place(1, 256, 256), None);
+        assert_eq!(cursor, Cursor { offset: 100 });
+        assert_eq!(cursor.place(156, 4, 256), Some(100));
+        assert_eq!(cursor.place(1, 1, 256), None);
+    }
+
+    #[test]
+    fn reset_starts_at_zero() {
+        let mut cursor = Cursor::default();
+        assert_eq!(cursor.place(200, 256, 256), Some(0));
+        cursor.reset();
+        assert_eq!(cursor.place(256, 256, 256), Some(0));
+    }
+}
@@ -0,0 +1,246 @@
+use crate::resources::{FaeResources, MemoryCategory};
+use ash::{version::InstanceV1_0, vk};
+
+// what VK_EXT_memory_budget reports for a heap, including memory used by other processes
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HeapBudget {
+    pub usage: u64,
+    pub budget: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HeapStats {
+    pub index: usize,
+    pub size: u64,
+    pub device_local: bool,
+    // vk-mem blocks allocated from this heap and the allocations placed in them
+    pub blocks: u32,
+    pub allocations: u32,
+    pub used_bytes: u64,
+    pub unused_bytes: u64,
+    // None without VK_EXT_memory_budget
+    pub budget: Option<HeapBudget>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryTypeStats {
+    pub index: usize,
+    pub heap_index: u32,
+    pub flags: vk::MemoryPropertyFlags,
+    pub blocks: u32,
+    pub allocations: u32,
+    pub used_bytes: u64,
+    pub unused_bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CategoryStats {
+    pub category: MemoryCategory,
+    pub count: usize,
+    // size of the allocations, which can be more than was asked for
+    pub bytes: u64,
+}
+
+// everything allocated through vk-mem at the time of the report, see Fae::memory_report
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryReport {
+    // only heaps and memory types vk-mem allocated from or that have a budget
+    pub heaps: Vec<HeapStats>,
+    pub types: Vec<MemoryTypeStats>,
+    pub categories: Vec<CategoryStats>,
+    // how the uniform and instance buffers of the last prepared frame were used
+    pub frame_uniform_bytes: u64,
+    pub frame_instance_bytes: u64,
+}
+
+impl MemoryReport {
+    pub(crate) fn collect(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        resources: &FaeResources,
+        budgets: Option<Vec<HeapBudget>>,
+    ) -> Result<MemoryReport, vk_mem::error::Error> {
+        let properties = unsafe { instance.get_physical_device_memory_properties(physical_device) };
+        let stats = resources.allocator.calculate_stats()?;
+
+        let heaps = (0..properties.memory_heap_count as usize)
+            .map(|index| {
+                let heap = properties.memory_heaps[index];
+                let info = &stats.memoryHeap[index];
+                HeapStats {
+                    index,
+                    size: heap.size,
+                    device_local: heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL),
+                    blocks: info.blockCount,
+                    allocations: info.allocationCount,
+                    used_bytes: info.usedBytes,
+                    unused_bytes: info.unusedBytes,
+                    budget: budgets.as_ref().and_then(|b| b.get(index)).copied(),
+                }
+            })
+            .filter(|heap| heap.blocks > 0 || heap.budget.is_some())
+            .collect();
+        let types = (0..properties.memory_type_count as usize)
+            .map(|index| {
+                let memory_type = properties.memory_types[index];
+                let info = &stats.memoryType[index];
+                MemoryTypeStats {
+                    index,
+                    heap_index: memory_type.heap_index,
+                    flags: memory_type.property_flags,
+                    blocks: info.blockCount,
+                    allocations: info.allocationCount,
+                    used_bytes: info.usedBytes,
+                    unused_bytes: info.unusedBytes,
+                }
+            })
+            .filter(|memory_type| memory_type.blocks > 0)
+            .collect();
+        let categories = resources
+            .usage_by_category()
+            .into_iter()
+            .map(|(category, count, bytes)| CategoryStats {
+                category,
+                count,
+                bytes,
+            })
+            .collect();
+        Ok(MemoryReport {
+            heaps,
+            types,
+            categories,
+            frame_uniform_bytes: 0,
+            frame_instance_bytes: 0,
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        let heaps: Vec<String> = self
+            .heaps
+            .iter()
+            .map(|h| {
+                let budget = match h.budget {
+                    Some(b) => format!("{{\"usage\":{},\"budget\":{}}}", b.usage, b.budget),
+                    None => "null".to_string(),
+                };
+                format!(
+                    "{{\"index\":{},\"size\":{},\"device_local\":{},\"blocks\":{},\"allocations\":{},\"used_bytes\":{},\"unused_bytes\":{},\"budget\":{}}}",
+                    h.index, h.size, h.device_local, h.blocks, h.allocations, h.used_bytes, h.unused_bytes, budget
+                )
+            })
+            .collect();
+        let types: Vec<String> = self
+            .types
+            .iter()
+            .map(|t| {
+                format!(
+                    "{{\"index\":{},\"heap_index\":{},\"flags\":\"{:?}\",\"blocks\":{},\"allocations\":{},\"used_bytes\":{},\"unused_bytes\":{}}}",
+                    t.index, t.heap_index, t.flags, t.blocks, t.allocations, t.used_bytes, t.unused_bytes
+                )
+            })
+            .collect();
+        let categories: Vec<String> = self
+            .categories
+            .iter()
+            .map(|c| {
+                format!(
+                    "{{\"category\":\"{}\",\"count\":{},\"bytes\":{}}}",
+                    c.category.name(),
+                    c.count,
+                    c.bytes
+                )
+            })
+            .collect();
+        format!(
+            "{{\"heaps\":[{}],\"types\":[{}],\"categories\":[{}],\"frame_uniform_bytes\":{},\"frame_instance_bytes\":{}}}",
+            heaps.join(","),
+            types.join(","),
+            categories.join(","),
+            self.frame_uniform_bytes,
+            self.frame_instance_bytes
+        )
+    }
+
+    pub fn write_json<P: AsRef<std::path::Path>>(&self, path: P) -> std::io::Result<()> {
+        std::fs::write(path, self.to_json())
+    }
+}
+
+fn mib(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+impl std::fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for h in &self.heaps {
+            write!(
+                f,
+                "heap {}{}: {:.2} MiB used, {:.2} MiB unused in {} blocks, {} allocations, {:.0} MiB total",
+                h.index,
+                if h.device_local { " (device local)" } else { "" },
+                mib(h.used_bytes),
+                mib(h.unused_bytes),
+                h.blocks,
+                h.allocations,
+                mib(h.size)
+            )?;
+            if let Some(budget) = h.budget {
+                write!(
+                    f,
+                    ", budget {:.2} of {:.2} MiB",
+                    mib(budget.usage),
+                    mib(budget.budget)
+                )?;
+            }
+            writeln!(f)?;
+        }
+        for t in &self.types {
+            writeln!(
+                f,
+                "  type {} (heap {}, {:?}): {:.2} MiB used, {:.2} MiB unused, {} allocations",
+                t.index,
+                t.heap_index,
+                t.flags,
+                mib(t.used_bytes),
+                mib(t.unused_bytes),
+                t.allocations
+            )?;
+        }
+        for c in &self.categories {
+            writeln!(
+                f,
+                "{}: {} resources, {:.2} MiB",
+                c.category.name(),
+                c.count,
+                mib(c.bytes)
+            )?;
+        }
+        write!(
+            f,
+            "last frame: {} bytes of uniforms, {} bytes of instances",
+            self.frame_uniform_bytes, self.frame_instance_bytes
+        )
+    }
+}
+
+// reads usage and budget of every heap through VK_EXT_memory_budget, the extension has to be
+// enabled on the device
+pub(crate) fn query_budgets(
+    properties2: &vk::KhrGetPhysicalDeviceProperties2Fn,
+    physical_device: vk::PhysicalDevice,
+) -> Vec<HeapBudget> {
+    let mut budget_properties = vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+    let mut properties = vk::PhysicalDeviceMemoryProperties2 {
+        p_next: &mut budget_properties as *mut _ as *mut std::ffi::c_void,
+        ..Default::default()
+    };
+    unsafe {
+        properties2.get_physical_device_memory_properties2_khr(physical_device, &mut properties)
+    };
+    (0..properties.memory_properties.memory_heap_count as usize)
+        .map(|index| HeapBudget {
+            usage: budget_properties.heap_usage[index],
+            budget: budget_properties.heap_budget[index],
+        })
+        .collect()
+}
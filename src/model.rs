@@ -1,19 +1,11 @@
 use crate::buffer::Buffer;
+use crate::error::FaeError;
+use crate::memory::{LinearAllocator, TransientSlice};
+use crate::render_pass_and_pipeline::WIREFRAME_PIPELINE;
+use crate::upload::FaeUploader;
+use crate::vertex_layout::{VertexInput, VertexLayout};
 use ash::{version::DeviceV1_0, vk};
 
-#[derive(Debug, Clone)]
-struct InvalidHandle;
-impl std::fmt::Display for InvalidHandle {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "invalid handle")
-    }
-}
-impl std::error::Error for InvalidHandle {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        None
-    }
-}
-
 pub struct Model<V, I> {
     vertex_data: Vec<V>,
     index_data: Vec<u32>,
@@ -24,7 +16,10 @@ pub struct Model<V, I> {
     next_handle: usize,
     pub vertex_buffer: Option<Buffer>,
     pub index_buffer: Option<Buffer>,
-    pub instance_buffer: Option<Buffer>,
+    // name of the Fae pipeline drawing this model, it has to match the vertex and instance types
+    pub pipeline: String,
+    // the vertex and index buffers are named after it
+    pub name: String,
 }
 
 #[allow(dead_code)]
@@ -43,7 +38,7 @@ impl<V, I> Model<V, I> {
             None
         }
     }
-    fn swap_by_handle(&mut self, handle1: usize, handle2: usize) -> Result<(), InvalidHandle> {
+    fn swap_by_handle(&mut self, handle1: usize, handle2: usize) -> Result<(), FaeError> {
         if handle1 == handle2 {
             return Ok(());
         }
@@ -56,8 +51,10 @@ impl<V, I> Model<V, I> {
             self.handle_to_index.insert(index1, handle2);
             self.handle_to_index.insert(index2, handle1);
             Ok(())
+        } else if self.handle_to_index.contains_key(&handle1) {
+            Err(FaeError::InvalidHandle(handle2))
         } else {
-            Err(InvalidHandle)
+            Err(FaeError::InvalidHandle(handle1))
         }
     }
     fn swap_by_index(&mut self, index1: usize, index2: usize) {
@@ -71,14 +68,14 @@ impl<V, I> Model<V, I> {
         self.handle_to_index.insert(index1, handle2);
         self.handle_to_index.insert(index2, handle1);
     }
-    fn is_visible(&self, handle: usize) -> Result<bool, InvalidHandle> {
+    fn is_visible(&self, handle: usize) -> Result<bool, FaeError> {
         if let Some(index) = self.handle_to_index.get(&handle) {
             Ok(index < &self.first_invisible)
         } else {
-            Err(InvalidHandle)
+            Err(FaeError::InvalidHandle(handle))
         }
     }
-    fn make_visible(&mut self, handle: usize) -> Result<(), InvalidHandle> {
+    fn make_visible(&mut self, handle: usize) -> Result<(), FaeError> {
         // if already visible do nothing
         if let Some(&index) = self.handle_to_index.get(&handle) {
             if index < self.first_invisible {
@@ -89,10 +86,10 @@ impl<V, I> Model<V, I> {
             self.first_invisible += 1;
             Ok(())
         } else {
-            Err(InvalidHandle)
+            Err(FaeError::InvalidHandle(handle))
         }
     }
-    fn make_invisible(&mut self, handle: usize) -> Result<(), InvalidHandle> {
+    fn make_invisible(&mut self, handle: usize) -> Result<(), FaeError> {
         // if already invisible do nothing
         if let Some(&index) = self.handle_to_index.get(&handle) {
             if index >= self.first_invisible {
@@ -103,7 +100,7 @@ impl<V, I> Model<V, I> {
             self.first_invisible -= 1;
             Ok(())
         } else {
-            Err(InvalidHandle)
+            Err(FaeError::InvalidHandle(handle))
         }
     }
     fn insert(&mut self, element: I) -> usize {
@@ -120,7 +117,7 @@ impl<V, I> Model<V, I> {
         self.make_visible(new_handle).ok(); //cant go wrong, see previous line
         new_handle
     }
-    fn remove(&mut self, handle: usize) -> Result<I, InvalidHandle> {
+    fn remove(&mut self, handle: usize) -> Result<I, FaeError> {
         if let Some(&index) = self.handle_to_index.get(&handle) {
             if index < self.first_invisible {
                 self.swap_by_index(index, self.first_invisible - 1);
@@ -130,111 +127,91 @@ impl<V, I> Model<V, I> {
             self.handles.pop();
             self.handle_to_index.remove(&handle);
             //must be Some(), otherwise we couldnt have found and index
-            Ok(self.instances.pop().unwrap())
+            self.instances.pop().ok_or(FaeError::InvalidHandle(handle))
         } else {
-            Err(InvalidHandle)
+            Err(FaeError::InvalidHandle(handle))
         }
     }
-    pub fn update_vertex_buffer(
-        &mut self,
-        allocator: &vk_mem::Allocator,
-    ) -> Result<(), vk_mem::error::Error> {
-        if let Some(buffer) = &mut self.vertex_buffer {
-            buffer.fill(allocator, &self.vertex_data)?;
-            Ok(())
-        } else {
-            let bytes = (self.vertex_data.len() * std::mem::size_of::<V>()) as u64;
-            let mut buffer = Buffer::new(
-                &allocator,
-                bytes,
-                vk::BufferUsageFlags::VERTEX_BUFFER,
-                vk_mem::MemoryUsage::CpuToGpu,
-            )?;
-            buffer.fill(allocator, &self.vertex_data)?;
-            self.vertex_buffer = Some(buffer);
-            Ok(())
-        }
+    // vertex and index data is static, it lives in device local memory and is uploaded on the
+    // transfer queue
+    pub fn update_vertex_buffer(&mut self, uploader: &FaeUploader) -> Result<(), FaeError> {
+        uploader.upload(
+            &mut self.vertex_buffer,
+            &format!("{} vertices", self.name),
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+            &self.vertex_data,
+        )
     }
-    pub fn update_index_buffer(
-        &mut self,
-        allocator: &vk_mem::Allocator,
-    ) -> Result<(), vk_mem::error::Error> {
-        if let Some(buffer) = &mut self.index_buffer {
-            buffer.fill(allocator, &self.index_data)?;
-            Ok(())
-        } else {
-            let bytes = (self.index_data.len() * std::mem::size_of::<u32>()) as u64;
-            let mut buffer = Buffer::new(
-                &allocator,
-                bytes,
-                vk::BufferUsageFlags::INDEX_BUFFER,
-                vk_mem::MemoryUsage::CpuToGpu,
-            )?;
-            buffer.fill(allocator, &self.index_data)?;
-            self.index_buffer = Some(buffer);
-            Ok(())
-        }
+    pub fn update_index_buffer(&mut self, uploader: &FaeUploader) -> Result<(), FaeError> {
+        uploader.upload(
+            &mut self.index_buffer,
+            &format!("{} indices", self.name),
+            vk::BufferUsageFlags::INDEX_BUFFER,
+            &self.index_data,
+        )
     }
-    pub fn update_instance_buffer(
-        &mut self,
-        allocator: &vk_mem::Allocator,
-    ) -> Result<(), vk_mem::error::Error> {
-        if let Some(buffer) = &mut self.instance_buffer {
-            buffer.fill(allocator, &self.instances[0..self.first_invisible])?;
-            Ok(())
-        } else {
-            let bytes = (self.first_invisible * std::mem::size_of::<I>()) as u64;
-            let mut buffer = Buffer::new(
-                &allocator,
-                bytes,
-                vk::BufferUsageFlags::VERTEX_BUFFER,
-                vk_mem::MemoryUsage::CpuToGpu,
-            )?;
-            buffer.fill(allocator, &self.instances[0..self.first_invisible])?;
-            self.instance_buffer = Some(buffer);
-            Ok(())
+    // copies the visible instances into this frame's instance buffer, None if nothing is visible
+    pub fn push_instances(
+        &self,
+        frame_allocator: &mut LinearAllocator,
+    ) -> Result<Option<TransientSlice>, FaeError> {
+        if self.first_invisible == 0 {
+            return Ok(None);
         }
+        let slice = frame_allocator.push(
+            &self.instances[0..self.first_invisible],
+            std::mem::align_of::<I>() as u64,
+        )?;
+        Ok(Some(slice))
     }
-    pub fn draw(&self, logical_device: &ash::Device, command_buffer: vk::CommandBuffer) {
+    pub fn draw(
+        &self,
+        logical_device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        instances: &TransientSlice,
+    ) {
         if let Some(vertex_buffer) = &self.vertex_buffer {
             if let Some(index_buffer) = &self.index_buffer {
-                if let Some(instance_buffer) = &self.instance_buffer {
-                    if self.first_invisible > 0 {
-                        unsafe {
-                            logical_device.cmd_bind_vertex_buffers(
-                                command_buffer,
-                                0,
-                                &[vertex_buffer.buffer],
-                                &[0],
-                            );
-                            logical_device.cmd_bind_vertex_buffers(
-                                command_buffer,
-                                1,
-                                &[instance_buffer.buffer],
-                                &[0],
-                            );
-                            logical_device.cmd_bind_index_buffer(
-                                command_buffer,
-                                index_buffer.buffer,
-                                0,
-                                vk::IndexType::UINT32,
-                            );
-                            logical_device.cmd_draw_indexed(
-                                command_buffer,
-                                self.index_data.len() as u32,
-                                self.first_invisible as u32,
-                                0,
-                                0,
-                                0,
-                            );
-                        }
-                    }
+                unsafe {
+                    logical_device.cmd_bind_vertex_buffers(
+                        command_buffer,
+                        0,
+                        &[vertex_buffer.buffer],
+                        &[0],
+                    );
+                    logical_device.cmd_bind_vertex_buffers(
+                        command_buffer,
+                        1,
+                        &[instances.buffer],
+                        &[instances.offset],
+                    );
+                    logical_device.cmd_bind_index_buffer(
+                        command_buffer,
+                        index_buffer.buffer,
+                        0,
+                        vk::IndexType::UINT32,
+                    );
+                    logical_device.cmd_draw_indexed(
+                        command_buffer,
+                        self.index_data.len() as u32,
+                        (instances.size / std::mem::size_of::<I>() as u64) as u32,
+                        0,
+                        0,
+                        0,
+                    );
                 }
             }
         }
     }
 }
 
+impl<V: VertexLayout, I: VertexLayout> Model<V, I> {
+    // what a pipeline drawing this kind of model reads from the vertex and instance buffers
+    pub fn vertex_input() -> VertexInput {
+        VertexInput::of::<V, I>()
+    }
+}
+
 impl Model<VertexData, InstanceData> {
     pub fn icosahedron() -> Model<VertexData, InstanceData> {
         let phi = (1.0 + 5.0_f32.sqrt()) / 2.0;
@@ -330,7 +307,8 @@ impl Model<VertexData, InstanceData> {
             next_handle: 0,
             vertex_buffer: None,
             index_buffer: None,
-            instance_buffer: None,
+            pipeline: WIREFRAME_PIPELINE.to_string(),
+            name: "icosahedron".to_string(),
         }
     }
 
@@ -342,11 +320,12 @@ impl Model<VertexData, InstanceData> {
         for v in &mut model.vertex_data {
             v.position = normalize(v.position);
         }
+        model.name = "sphere".to_string();
         model
     }
 
-    #[allow(dead_code)]
-    pub fn cube() -> Model<[f32; 3], InstanceData> {
+    // every face has its own vertices so it can have a flat normal
+    pub fn cube() -> Model<VertexData, InstanceData> {
         let lbf = [-1.0, 1.0, -1.0]; //lbf: left-bottom-front
         let lbb = [-1.0, 1.0, 1.0];
         let ltf = [-1.0, -1.0, -1.0];
@@ -355,16 +334,27 @@ impl Model<VertexData, InstanceData> {
         let rbb = [1.0, 1.0, 1.0];
         let rtf = [1.0, -1.0, -1.0];
         let rtb = [1.0, -1.0, 1.0];
+        let corners = [lbf, lbb, ltf, ltb, rbf, rbb, rtf, rtb];
+        let faces = [
+            ([0, 1, 5, 0, 5, 4], [0.0, 1.0, 0.0]),  //bottom
+            ([2, 7, 3, 2, 6, 7], [0.0, -1.0, 0.0]), //top
+            ([0, 6, 2, 0, 4, 6], [0.0, 0.0, -1.0]), //front
+            ([1, 3, 7, 1, 7, 5], [0.0, 0.0, 1.0]),  //back
+            ([0, 2, 1, 1, 2, 3], [-1.0, 0.0, 0.0]), //left
+            ([4, 5, 6, 5, 7, 6], [1.0, 0.0, 0.0]),  //right
+        ];
+        let mut vertex_data = Vec::with_capacity(36);
+        for (triangles, normal) in faces.iter() {
+            for &corner in triangles {
+                vertex_data.push(VertexData {
+                    position: corners[corner],
+                    normal: *normal,
+                });
+            }
+        }
         Model {
-            vertex_data: vec![lbf, lbb, ltf, ltb, rbf, rbb, rtf, rtb],
-            index_data: vec![
-                0, 1, 5, 0, 5, 4, //bottom
-                2, 7, 3, 2, 6, 7, //top
-                0, 6, 2, 0, 4, 6, //front
-                1, 3, 7, 1, 7, 5, //back
-                0, 2, 1, 1, 2, 3, //left
-                4, 5, 6, 5, 7, 6, //right
-            ],
+            index_data: (0..vertex_data.len() as u32).collect(),
+            vertex_data,
             handle_to_index: std::collections::HashMap::new(),
             handles: Vec::new(),
             instances: Vec::new(),
@@ -372,7 +362,8 @@ impl Model<VertexData, InstanceData> {
             next_handle: 0,
             vertex_buffer: None,
             index_buffer: None,
-            instance_buffer: None,
+            pipeline: WIREFRAME_PIPELINE.to_string(),
+            name: "cube".to_string(),
         }
     }
 
@@ -436,12 +427,22 @@ impl InstanceData {
     ) -> InstanceData {
         InstanceData {
             model_matrix: model_matrix.into(),
-            inverse_model_matrix: model_matrix.try_inverse().unwrap().into(),
+            // a singular matrix squashes the instance flat, its normals do not matter then
+            inverse_model_matrix: model_matrix
+                .try_inverse()
+                .unwrap_or_else(nalgebra::Matrix4::zeros)
+                .into(),
             color,
         }
     }
 }
 
+crate::impl_vertex_layout!(InstanceData {
+    model_matrix,
+    inverse_model_matrix,
+    color
+});
+
 #[derive(Copy, Clone, Debug)]
 #[repr(C)]
 pub struct VertexData {
@@ -449,6 +450,8 @@ pub struct VertexData {
     pub normal: [f32; 3],
 }
 
+crate::impl_vertex_layout!(VertexData { position, normal });
+
 impl VertexData {
     fn midpoint(a: &VertexData, b: &VertexData) -> VertexData {
         VertexData {
@@ -0,0 +1,67 @@
+use crate::error::{Context, FaeError};
+use crate::resources::{FaeResources, Image};
+use ash::{version::DeviceV1_0, vk};
+use std::rc::Rc;
+
+// render target used instead of the swapchain when there is no window
+pub struct FaeOffscreen {
+    pub color: Image,
+    depth: Image,
+    pub framebuffer: vk::Framebuffer,
+    pub format: vk::Format,
+    pub extent: vk::Extent2D,
+}
+
+impl FaeOffscreen {
+    pub fn init(
+        resources: &Rc<FaeResources>,
+        extent: vk::Extent2D,
+    ) -> Result<FaeOffscreen, FaeError> {
+        let format = vk::Format::R8G8B8A8_UNORM;
+        // color image is copied out after rendering so it needs to be a transfer source
+        let color = Image::new(
+            resources,
+            extent,
+            format,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+            vk::ImageAspectFlags::COLOR,
+        )?;
+        let depth = Image::new(
+            resources,
+            extent,
+            vk::Format::D32_SFLOAT,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            vk::ImageAspectFlags::DEPTH,
+        )?;
+
+        Ok(FaeOffscreen {
+            color,
+            depth,
+            framebuffer: vk::Framebuffer::null(),
+            format,
+            extent,
+        })
+    }
+
+    pub fn create_framebuffer(
+        &mut self,
+        logical_device: &ash::Device,
+        render_pass: vk::RenderPass,
+    ) -> Result<(), FaeError> {
+        let i_view = [self.color.view, self.depth.view];
+        let framebuffer_info = vk::FramebufferCreateInfo::builder()
+            .render_pass(render_pass)
+            .attachments(&i_view)
+            .width(self.extent.width)
+            .height(self.extent.height)
+            .layers(1);
+        self.framebuffer = unsafe { logical_device.create_framebuffer(&framebuffer_info, None) }
+            .context("creating the offscreen framebuffer")?;
+        Ok(())
+    }
+
+    // the images are released when the offscreen target is dropped
+    pub unsafe fn cleanup(&mut self, logical_device: &ash::Device) {
+        logical_device.destroy_framebuffer(self.framebuffer, None);
+    }
+}
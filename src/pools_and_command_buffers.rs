@@ -1,8 +1,8 @@
 use crate::instance_device_queues::QueueFamilies;
 use ash::{version::DeviceV1_0, vk};
 pub struct Pools {
-    command_pool_graphics: vk::CommandPool,
-    command_pool_transfer: vk::CommandPool,
+    pub command_pool_graphics: vk::CommandPool,
+    pub command_pool_transfer: vk::CommandPool,
 }
 
 impl Pools {
@@ -11,13 +11,13 @@ impl Pools {
         queue_families: &QueueFamilies,
     ) -> Result<Pools, vk::Result> {
         let graphics_command_pool_create_info = vk::CommandPoolCreateInfo::builder()
-            .queue_family_index(queue_families.graphics_q_index.unwrap())
+            .queue_family_index(queue_families.graphics_q_index)
             .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER);
         let command_pool_graphics = unsafe {
             logical_device.create_command_pool(&graphics_command_pool_create_info, None)
         }?;
         let transfer_command_pool_create_info = vk::CommandPoolCreateInfo::builder()
-            .queue_family_index(queue_families.transfer_q_index.unwrap())
+            .queue_family_index(queue_families.transfer_q_index)
             .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER);
         let command_pool_transfer = unsafe {
             logical_device.create_command_pool(&transfer_command_pool_create_info, None)
@@ -0,0 +1,221 @@
+use ash::{
+    version::{DeviceV1_0, InstanceV1_0},
+    vk,
+};
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+
+// regions that can be timed per frame, the render pass plus one per model
+const MAX_REGIONS: u32 = 64;
+// number of frames the rolling averages are taken over
+const AVERAGE_WINDOW: usize = 60;
+
+// times regions of the command buffer with timestamp queries. Every frame in flight has its
+// own range of queries, the results are read when that frame's fence was waited on, so
+// reading them never stalls.
+pub struct FaeProfiler {
+    query_pool: vk::QueryPool,
+    // nanoseconds per timestamp tick
+    timestamp_period: f64,
+    timestamp_mask: u64,
+    // names of the regions recorded into each frame, region i uses queries 2i and 2i + 1
+    regions: Vec<Vec<String>>,
+    averages: HashMap<String, VecDeque<f64>>,
+    // order the regions were first seen in, so reports are stable
+    region_order: Vec<String>,
+    frames_collected: u64,
+    csv: Option<std::io::BufWriter<std::fs::File>>,
+}
+
+impl FaeProfiler {
+    // None if the graphics queue does not support timestamps
+    pub fn init(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        logical_device: &ash::Device,
+        graphics_queue_family: u32,
+        frames_in_flight: usize,
+    ) -> Result<Option<FaeProfiler>, vk::Result> {
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let queue_family_properties =
+            unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+        let valid_bits =
+            queue_family_properties[graphics_queue_family as usize].timestamp_valid_bits;
+        if valid_bits == 0 {
+            return Ok(None);
+        }
+        let timestamp_mask = if valid_bits >= 64 {
+            std::u64::MAX
+        } else {
+            (1 << valid_bits) - 1
+        };
+        let query_pool_info = vk::QueryPoolCreateInfo::builder()
+            .query_type(vk::QueryType::TIMESTAMP)
+            .query_count(2 * MAX_REGIONS * frames_in_flight as u32);
+        let query_pool = unsafe { logical_device.create_query_pool(&query_pool_info, None) }?;
+        Ok(Some(FaeProfiler {
+            query_pool,
+            timestamp_period: properties.limits.timestamp_period as f64,
+            timestamp_mask,
+            regions: vec![vec![]; frames_in_flight],
+            averages: HashMap::new(),
+            region_order: vec![],
+            frames_collected: 0,
+            csv: None,
+        }))
+    }
+
+    // writes one line per region and frame to the file, "frame,region,milliseconds"
+    pub fn enable_csv<P: AsRef<std::path::Path>>(&mut self, path: P) -> std::io::Result<()> {
+        let mut csv = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(csv, "frame,region,milliseconds")?;
+        self.csv = Some(csv);
+        Ok(())
+    }
+
+    pub fn disable_csv(&mut self) -> std::io::Result<()> {
+        if let Some(mut csv) = self.csv.take() {
+            csv.flush()?;
+        }
+        Ok(())
+    }
+
+    fn first_query(&self, frame: usize) -> u32 {
+        frame as u32 * 2 * MAX_REGIONS
+    }
+
+    // has to be recorded outside of a render pass before any region of the frame
+    pub fn begin_frame(
+        &mut self,
+        logical_device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+    ) {
+        self.regions[frame].clear();
+        unsafe {
+            logical_device.cmd_reset_query_pool(
+                command_buffer,
+                self.query_pool,
+                self.first_query(frame),
+                2 * MAX_REGIONS,
+            );
+        }
+    }
+
+    // starts timing a region, None if there are too many regions in this frame
+    pub fn begin_region(
+        &mut self,
+        logical_device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        name: &str,
+    ) -> Option<usize> {
+        let region = self.regions[frame].len();
+        if region as u32 >= MAX_REGIONS {
+            return None;
+        }
+        self.regions[frame].push(name.to_string());
+        unsafe {
+            logical_device.cmd_write_timestamp(
+                command_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                self.query_pool,
+                self.first_query(frame) + 2 * region as u32,
+            );
+        }
+        Some(region)
+    }
+
+    pub fn end_region(
+        &mut self,
+        logical_device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        region: Option<usize>,
+    ) {
+        if let Some(region) = region {
+            unsafe {
+                logical_device.cmd_write_timestamp(
+                    command_buffer,
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    self.query_pool,
+                    self.first_query(frame) + 2 * region as u32 + 1,
+                );
+            }
+        }
+    }
+
+    // reads the timings of the last submission of this frame, only call this after waiting for
+    // the frame's fence
+    pub fn collect(&mut self, logical_device: &ash::Device, frame: usize) {
+        if self.regions[frame].is_empty() {
+            return;
+        }
+        let regions = std::mem::replace(&mut self.regions[frame], vec![]);
+        let mut timestamps = vec![0u64; 2 * regions.len()];
+        let result = unsafe {
+            logical_device.get_query_pool_results(
+                self.query_pool,
+                self.first_query(frame),
+                timestamps.len() as u32,
+                &mut timestamps,
+                vk::QueryResultFlags::TYPE_64,
+            )
+        };
+        // NOT_READY if the frame was never submitted, nothing to report then
+        if result.is_err() {
+            return;
+        }
+        self.frames_collected += 1;
+        for (name, pair) in regions.iter().zip(timestamps.chunks(2)) {
+            let ticks = (pair[1].wrapping_sub(pair[0])) & self.timestamp_mask;
+            let milliseconds = ticks as f64 * self.timestamp_period / 1_000_000.0;
+            if !self.averages.contains_key(name) {
+                self.region_order.push(name.clone());
+            }
+            let samples = self
+                .averages
+                .entry(name.clone())
+                .or_insert_with(VecDeque::new);
+            if samples.len() == AVERAGE_WINDOW {
+                samples.pop_front();
+            }
+            samples.push_back(milliseconds);
+            if let Some(csv) = &mut self.csv {
+                if writeln!(csv, "{},{},{}", self.frames_collected, name, milliseconds).is_err() {
+                    log::warn!("could not write gpu timings, csv output disabled");
+                    self.csv = None;
+                }
+            }
+        }
+    }
+
+    // average gpu time in milliseconds of every region over the last frames
+    pub fn averages(&self) -> Vec<(String, f64)> {
+        self.region_order
+            .iter()
+            .map(|name| {
+                let samples = &self.averages[name];
+                let average = samples.iter().sum::<f64>() / samples.len() as f64;
+                (name.clone(), average)
+            })
+            .collect()
+    }
+
+    // keeps the averages and csv output of a profiler whose device was lost
+    pub(crate) fn carry_over(&mut self, lost: FaeProfiler) {
+        self.averages = lost.averages;
+        self.region_order = lost.region_order;
+        self.frames_collected = lost.frames_collected;
+        self.csv = lost.csv;
+    }
+
+    pub(crate) unsafe fn destroy_queries(&self, logical_device: &ash::Device) {
+        logical_device.destroy_query_pool(self.query_pool, None);
+    }
+
+    pub unsafe fn cleanup(&mut self, logical_device: &ash::Device) {
+        let _ = self.disable_csv();
+        self.destroy_queries(logical_device);
+    }
+}
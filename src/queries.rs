@@ -0,0 +1,280 @@
+use ash::{version::DeviceV1_0, vk};
+
+// models that get their own queries per frame, later models are drawn without them
+const MAX_MODELS: u32 = 64;
+// the counters of a pipeline statistics query, in the order vulkan writes them
+const STATISTICS: [vk::QueryPipelineStatisticFlags; 5] = [
+    vk::QueryPipelineStatisticFlags::INPUT_ASSEMBLY_VERTICES,
+    vk::QueryPipelineStatisticFlags::INPUT_ASSEMBLY_PRIMITIVES,
+    vk::QueryPipelineStatisticFlags::CLIPPING_INVOCATIONS,
+    vk::QueryPipelineStatisticFlags::CLIPPING_PRIMITIVES,
+    vk::QueryPipelineStatisticFlags::FRAGMENT_SHADER_INVOCATIONS,
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PipelineStatistics {
+    pub vertices: u64,
+    pub primitives: u64,
+    // primitives that reached the clipping stage and how many came out of it
+    pub clipping_invocations: u64,
+    pub clipping_primitives: u64,
+    pub fragment_shader_invocations: u64,
+}
+
+impl PipelineStatistics {
+    fn from_counters(counters: [u64; 5]) -> PipelineStatistics {
+        PipelineStatistics {
+            vertices: counters[0],
+            primitives: counters[1],
+            clipping_invocations: counters[2],
+            clipping_primitives: counters[3],
+            fragment_shader_invocations: counters[4],
+        }
+    }
+}
+
+impl std::ops::Add for PipelineStatistics {
+    type Output = PipelineStatistics;
+    fn add(self, other: PipelineStatistics) -> PipelineStatistics {
+        PipelineStatistics {
+            vertices: self.vertices + other.vertices,
+            primitives: self.primitives + other.primitives,
+            clipping_invocations: self.clipping_invocations + other.clipping_invocations,
+            clipping_primitives: self.clipping_primitives + other.clipping_primitives,
+            fragment_shader_invocations: self.fragment_shader_invocations
+                + other.fragment_shader_invocations,
+        }
+    }
+}
+
+// results for the instanced draw of one model
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ModelStatistics {
+    // None if the device does not support pipeline statistics queries
+    pub pipeline: Option<PipelineStatistics>,
+    // samples that passed the depth test, 0 means no instance of the model was visible
+    pub samples_passed: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrameStatistics {
+    // in the order of Fae::models, repeated for every view after the first
+    pub models: Vec<ModelStatistics>,
+    // sum over all models, statistics queries of one type can not be nested
+    pub total: Option<PipelineStatistics>,
+}
+
+// pipeline statistics and occlusion queries around every model draw. Like the profiler every
+// frame in flight has its own queries which are read after the frame's fence was waited on.
+pub struct FaeQueries {
+    // None if pipeline_statistics_query is not supported
+    statistics_pool: Option<vk::QueryPool>,
+    occlusion_pool: vk::QueryPool,
+    occlusion_flags: vk::QueryControlFlags,
+    // number of models that got queries in each frame in flight
+    recorded: Vec<u32>,
+    last_frame: Option<FrameStatistics>,
+}
+
+impl FaeQueries {
+    pub fn init(
+        logical_device: &ash::Device,
+        frames_in_flight: usize,
+        pipeline_statistics: bool,
+        precise_occlusion: bool,
+    ) -> Result<FaeQueries, vk::Result> {
+        let query_count = MAX_MODELS * frames_in_flight as u32;
+        let statistics_pool = if pipeline_statistics {
+            let statistics = STATISTICS
+                .iter()
+                .fold(vk::QueryPipelineStatisticFlags::empty(), |all, s| all | *s);
+            let query_pool_info = vk::QueryPoolCreateInfo::builder()
+                .query_type(vk::QueryType::PIPELINE_STATISTICS)
+                .pipeline_statistics(statistics)
+                .query_count(query_count);
+            Some(unsafe { logical_device.create_query_pool(&query_pool_info, None) }?)
+        } else {
+            None
+        };
+        let query_pool_info = vk::QueryPoolCreateInfo::builder()
+            .query_type(vk::QueryType::OCCLUSION)
+            .query_count(query_count);
+        let occlusion_pool = unsafe { logical_device.create_query_pool(&query_pool_info, None) }?;
+        // without precise queries any nonzero count only means something was visible
+        let occlusion_flags = if precise_occlusion {
+            vk::QueryControlFlags::PRECISE
+        } else {
+            vk::QueryControlFlags::empty()
+        };
+        Ok(FaeQueries {
+            statistics_pool,
+            occlusion_pool,
+            occlusion_flags,
+            recorded: vec![0; frames_in_flight],
+            last_frame: None,
+        })
+    }
+
+    fn first_query(frame: usize) -> u32 {
+        frame as u32 * MAX_MODELS
+    }
+
+    // has to be recorded outside of a render pass
+    pub fn begin_frame(
+        &mut self,
+        logical_device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+    ) {
+        self.recorded[frame] = 0;
+        let first_query = FaeQueries::first_query(frame);
+        unsafe {
+            if let Some(statistics_pool) = self.statistics_pool {
+                logical_device.cmd_reset_query_pool(
+                    command_buffer,
+                    statistics_pool,
+                    first_query,
+                    MAX_MODELS,
+                );
+            }
+            logical_device.cmd_reset_query_pool(
+                command_buffer,
+                self.occlusion_pool,
+                first_query,
+                MAX_MODELS,
+            );
+        }
+    }
+
+    // starts the queries for the next model, None once MAX_MODELS were recorded this frame
+    pub fn begin_model(
+        &mut self,
+        logical_device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+    ) -> Option<u32> {
+        if self.recorded[frame] >= MAX_MODELS {
+            return None;
+        }
+        let query = FaeQueries::first_query(frame) + self.recorded[frame];
+        self.recorded[frame] += 1;
+        unsafe {
+            if let Some(statistics_pool) = self.statistics_pool {
+                logical_device.cmd_begin_query(
+                    command_buffer,
+                    statistics_pool,
+                    query,
+                    vk::QueryControlFlags::empty(),
+                );
+            }
+            logical_device.cmd_begin_query(
+                command_buffer,
+                self.occlusion_pool,
+                query,
+                self.occlusion_flags,
+            );
+        }
+        Some(query)
+    }
+
+    pub fn end_model(
+        &self,
+        logical_device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        query: Option<u32>,
+    ) {
+        if let Some(query) = query {
+            unsafe {
+                logical_device.cmd_end_query(command_buffer, self.occlusion_pool, query);
+                if let Some(statistics_pool) = self.statistics_pool {
+                    logical_device.cmd_end_query(command_buffer, statistics_pool, query);
+                }
+            }
+        }
+    }
+
+    // reads the results of the last submission of this frame, only call this after waiting for
+    // the frame's fence
+    pub fn collect(&mut self, logical_device: &ash::Device, frame: usize) {
+        let count = self.recorded[frame];
+        if count == 0 {
+            return;
+        }
+        self.recorded[frame] = 0;
+        let first_query = FaeQueries::first_query(frame);
+        let mut samples = vec![0u64; count as usize];
+        let occlusion_result = unsafe {
+            logical_device.get_query_pool_results(
+                self.occlusion_pool,
+                first_query,
+                count,
+                &mut samples,
+                vk::QueryResultFlags::TYPE_64,
+            )
+        };
+        if occlusion_result.is_err() {
+            return;
+        }
+        let mut pipeline = vec![None; count as usize];
+        if let Some(statistics_pool) = self.statistics_pool {
+            // ash's wrapper only takes results of up to one u64 per query, every statistics
+            // query has a u64 per counter so the function is called directly with that stride
+            let counters_per_query = STATISTICS.len();
+            let mut counters = vec![0u64; count as usize * counters_per_query];
+            let stride = (counters_per_query * std::mem::size_of::<u64>()) as vk::DeviceSize;
+            let statistics_result = unsafe {
+                logical_device.fp_v1_0().get_query_pool_results(
+                    logical_device.handle(),
+                    statistics_pool,
+                    first_query,
+                    count,
+                    counters.len() * std::mem::size_of::<u64>(),
+                    counters.as_mut_ptr() as *mut std::ffi::c_void,
+                    stride,
+                    vk::QueryResultFlags::TYPE_64,
+                )
+            };
+            if statistics_result == vk::Result::SUCCESS {
+                pipeline = counters
+                    .chunks(counters_per_query)
+                    .map(|c| {
+                        let mut query = [0u64; 5];
+                        query.copy_from_slice(c);
+                        Some(PipelineStatistics::from_counters(query))
+                    })
+                    .collect();
+            }
+        }
+        let models: Vec<ModelStatistics> = samples
+            .into_iter()
+            .zip(pipeline)
+            .map(|(samples_passed, pipeline)| ModelStatistics {
+                pipeline,
+                samples_passed,
+            })
+            .collect();
+        let total = models
+            .iter()
+            .map(|m| m.pipeline)
+            .fold(Some(PipelineStatistics::default()), |total, m| {
+                Some(total? + m?)
+            });
+        self.last_frame = Some(FrameStatistics { models, total });
+    }
+
+    // results of the most recent frame that finished on the gpu
+    pub fn last_frame(&self) -> Option<&FrameStatistics> {
+        self.last_frame.as_ref()
+    }
+
+    pub fn has_pipeline_statistics(&self) -> bool {
+        self.statistics_pool.is_some()
+    }
+
+    pub unsafe fn cleanup(&self, logical_device: &ash::Device) {
+        if let Some(statistics_pool) = self.statistics_pool {
+            logical_device.destroy_query_pool(statistics_pool, None);
+        }
+        logical_device.destroy_query_pool(self.occlusion_pool, None);
+    }
+}
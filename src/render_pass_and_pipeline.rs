@@ -1,20 +1,27 @@
-use crate::swapchain::FaeSwapchain;
+use crate::debug::FaeDebug;
+use crate::error::{Context, FaeError};
+use crate::model::{InstanceData, Model, VertexData};
+use crate::shaders::{load_shader, parent_directory, ShaderStage};
+use crate::vertex_layout::{VertexInput, VertexLayout};
 use ash::{version::DeviceV1_0, vk};
+use std::path::{Path, PathBuf};
 pub fn init_render_pass(
     logical_device: &ash::Device,
     format: vk::Format,
+    final_layout: vk::ImageLayout,
 ) -> Result<vk::RenderPass, vk::Result> {
     // create attachments (essentially render-target)
     let attachments = [
         vk::AttachmentDescription::builder()
-            // format must be same as swapchain
+            // format must be same as the swapchain or offscreen image
             .format(format)
             .load_op(vk::AttachmentLoadOp::CLEAR)
             .store_op(vk::AttachmentStoreOp::STORE)
             .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
             .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
             .initial_layout(vk::ImageLayout::UNDEFINED)
-            .final_layout(vk::ImageLayout::PRESENT_SRC_KHR)
+            // PRESENT_SRC_KHR for the swapchain, TRANSFER_SRC_OPTIMAL for offscreen readback
+            .final_layout(final_layout)
             .samples(vk::SampleCountFlags::TYPE_1)
             .build(),
         //attachment for depth
@@ -66,186 +73,188 @@ pub fn init_render_pass(
     Ok(render_pass)
 }
 
-pub struct Pipeline {
-    pub pipeline: vk::Pipeline,
-    pub layout: vk::PipelineLayout,
-    pub descriptor_set_layouts: Vec<vk::DescriptorSetLayout>,
+// names of the pipelines every Fae starts with, models are drawn in wireframe by default
+pub const WIREFRAME_PIPELINE: &str = "wireframe";
+pub const SOLID_PIPELINE: &str = "solid";
+
+// how transparent fragments are combined with what is already in the color attachment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    Opaque,
+    Alpha,
+    Additive,
 }
 
-impl Pipeline {
-    pub fn init(
-        logical_device: &ash::Device,
-        swapchain: &FaeSwapchain,
-        render_pass: &vk::RenderPass,
-    ) -> Result<Pipeline, vk::Result> {
-        // create vertex shader module
-        let vertex_shader_create_info = vk::ShaderModuleCreateInfo::builder()
-            .code(vk_shader_macros::include_glsl!("./shaders/shader.vert"));
-        let vertex_shader_module =
-            unsafe { logical_device.create_shader_module(&vertex_shader_create_info, None)? };
-        // create fragment shader module
-        let fragment_shader_create_info = vk::ShaderModuleCreateInfo::builder()
-            .code(vk_shader_macros::include_glsl!("./shaders/shader.frag"));
-        let fragment_shader_module =
-            unsafe { logical_device.create_shader_module(&fragment_shader_create_info, None)? };
-        // define what functiuon should be used as the entry point in the shader
-        let main_function_name = std::ffi::CString::new("main").unwrap();
-        // shader stage creation info
-        let vertex_shader_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
-            .stage(vk::ShaderStageFlags::VERTEX)
-            .module(vertex_shader_module)
-            .name(&main_function_name);
-        let fragment_shader_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
-            .stage(vk::ShaderStageFlags::FRAGMENT)
-            .module(fragment_shader_module)
-            .name(&main_function_name);
-        // create shader stages
-        let shader_stages = vec![
-            vertex_shader_stage_create_info.build(),
-            fragment_shader_stage_create_info.build(),
-        ];
+// fixed function state and shaders of a graphics pipeline. Every pipeline uses the layout of
+// Pipelines, so the shaders can only use the camera in set 0.
+#[derive(Debug, Clone)]
+pub struct PipelineBuilder {
+    vertex_shader: Vec<u32>,
+    fragment_shader: Vec<u32>,
+    // vertex and fragment shader the code is loaded from, see Fae::reload_shaders
+    shader_files: Option<(PathBuf, PathBuf)>,
+    vertex_input: VertexInput,
+    topology: vk::PrimitiveTopology,
+    polygon_mode: vk::PolygonMode,
+    cull_mode: vk::CullModeFlags,
+    front_face: vk::FrontFace,
+    depth_test: bool,
+    depth_write: bool,
+    depth_compare: vk::CompareOp,
+    blend: BlendMode,
+    dynamic_states: Vec<vk::DynamicState>,
+}
 
-        //setup data to pass to vertex shader
-        let vertex_attrib_descs = [
-            vk::VertexInputAttributeDescription {
-                binding: 0,
-                location: 0,
-                offset: 0,
-                format: vk::Format::R32G32B32A32_SFLOAT,
-            },
-            vk::VertexInputAttributeDescription {
-                binding: 0,
-                location: 1,
-                offset: 12,
-                format: vk::Format::R32G32B32_SFLOAT,
-            },
-            vk::VertexInputAttributeDescription {
-                binding: 1,
-                location: 2,
-                offset: 0,
-                format: vk::Format::R32G32B32A32_SFLOAT,
-            },
-            vk::VertexInputAttributeDescription {
-                binding: 1,
-                location: 3,
-                offset: 16,
-                format: vk::Format::R32G32B32A32_SFLOAT,
-            },
-            vk::VertexInputAttributeDescription {
-                binding: 1,
-                location: 4,
-                offset: 32,
-                format: vk::Format::R32G32B32A32_SFLOAT,
-            },
-            vk::VertexInputAttributeDescription {
-                binding: 1,
-                location: 5,
-                offset: 48,
-                format: vk::Format::R32G32B32A32_SFLOAT,
-            },
-            vk::VertexInputAttributeDescription {
-                binding: 1,
-                location: 6,
-                offset: 64,
-                format: vk::Format::R32G32B32A32_SFLOAT,
-            },
-            vk::VertexInputAttributeDescription {
-                binding: 1,
-                location: 7,
-                offset: 80,
-                format: vk::Format::R32G32B32A32_SFLOAT,
-            },
-            vk::VertexInputAttributeDescription {
-                binding: 1,
-                location: 8,
-                offset: 96,
-                format: vk::Format::R32G32B32A32_SFLOAT,
-            },
-            vk::VertexInputAttributeDescription {
-                binding: 1,
-                location: 9,
-                offset: 112,
-                format: vk::Format::R32G32B32A32_SFLOAT,
-            },
-            vk::VertexInputAttributeDescription {
-                binding: 1,
-                location: 10,
-                offset: 128,
-                format: vk::Format::R32G32B32_SFLOAT,
-            },
-        ];
+impl PipelineBuilder {
+    // spir-v code, for example from vk_shader_macros::include_glsl!
+    pub fn shaders(mut self, vertex: &[u32], fragment: &[u32]) -> PipelineBuilder {
+        self.vertex_shader = vertex.to_vec();
+        self.fragment_shader = fragment.to_vec();
+        self.shader_files = None;
+        self
+    }
+    // .spv or glsl files, loaded when the pipeline is added and again whenever a file in
+    // their directories changes, see load_shader
+    pub fn shader_files<P: AsRef<Path>, Q: AsRef<Path>>(
+        mut self,
+        vertex: P,
+        fragment: Q,
+    ) -> PipelineBuilder {
+        self.vertex_shader = vec![];
+        self.fragment_shader = vec![];
+        self.shader_files = Some((
+            vertex.as_ref().to_path_buf(),
+            fragment.as_ref().to_path_buf(),
+        ));
+        self
+    }
+    // reads the shader files again, without files the code stays as it is
+    pub(crate) fn load_shaders(&mut self) -> Result<(), FaeError> {
+        if let Some((vertex, fragment)) = &self.shader_files {
+            let vertex_shader = load_shader(vertex, ShaderStage::Vertex)?;
+            let fragment_shader = load_shader(fragment, ShaderStage::Fragment)?;
+            self.vertex_shader = vertex_shader;
+            self.fragment_shader = fragment_shader;
+        }
+        Ok(())
+    }
+    // where the shader files are, empty for shaders given as code
+    pub(crate) fn shader_directories(&self) -> Vec<PathBuf> {
+        let mut directories = vec![];
+        if let Some((vertex, fragment)) = &self.shader_files {
+            for file in &[vertex, fragment] {
+                let directory = parent_directory(file);
+                if !directories.contains(&directory) {
+                    directories.push(directory);
+                }
+            }
+        }
+        directories
+    }
+    // the pipeline draws models with these vertex and instance types
+    pub fn vertex_layout<V: VertexLayout, I: VertexLayout>(mut self) -> PipelineBuilder {
+        self.vertex_input = VertexInput::of::<V, I>();
+        self
+    }
+    pub fn topology(mut self, topology: vk::PrimitiveTopology) -> PipelineBuilder {
+        self.topology = topology;
+        self
+    }
+    // LINE and POINT need the fill_mode_non_solid feature, which Fae always enables
+    pub fn polygon_mode(mut self, polygon_mode: vk::PolygonMode) -> PipelineBuilder {
+        self.polygon_mode = polygon_mode;
+        self
+    }
+    pub fn cull_mode(mut self, cull_mode: vk::CullModeFlags) -> PipelineBuilder {
+        self.cull_mode = cull_mode;
+        self
+    }
+    pub fn front_face(mut self, front_face: vk::FrontFace) -> PipelineBuilder {
+        self.front_face = front_face;
+        self
+    }
+    pub fn depth_test(mut self, enable: bool) -> PipelineBuilder {
+        self.depth_test = enable;
+        self
+    }
+    pub fn depth_write(mut self, enable: bool) -> PipelineBuilder {
+        self.depth_write = enable;
+        self
+    }
+    pub fn depth_compare(mut self, compare_op: vk::CompareOp) -> PipelineBuilder {
+        self.depth_compare = compare_op;
+        self
+    }
+    pub fn blend(mut self, blend: BlendMode) -> PipelineBuilder {
+        self.blend = blend;
+        self
+    }
+    // state that is set while recording instead, the draw code has to set it before drawing.
+    // Viewport and scissor are always dynamic.
+    pub fn dynamic_state(mut self, state: vk::DynamicState) -> PipelineBuilder {
+        if !self.dynamic_states.contains(&state) {
+            self.dynamic_states.push(state);
+        }
+        self
+    }
+}
 
-        let vertex_binding_descs = [
-            vk::VertexInputBindingDescription {
-                binding: 0,
-                stride: 24,
-                input_rate: vk::VertexInputRate::VERTEX,
-            },
-            vk::VertexInputBindingDescription {
-                binding: 1,
-                stride: 140,
-                input_rate: vk::VertexInputRate::INSTANCE,
-            },
-        ];
-        // shader input creation info
-        let vertex_input_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
-            .vertex_attribute_descriptions(&vertex_attrib_descs)
-            .vertex_binding_descriptions(&vertex_binding_descs);
-        // is topology points or triangles
-        let input_assembly_create_info = vk::PipelineInputAssemblyStateCreateInfo::builder()
-            .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
-        // define what part of screen to correspond to internal coordinates
-        let viewports = [vk::Viewport {
-            x: 0.,
-            y: 0.,
-            width: swapchain.extent.width as f32,
-            height: swapchain.extent.height as f32,
-            min_depth: 0.,
-            max_depth: 1.,
-        }];
-        // define area that we can draw in
-        let scissors = [vk::Rect2D {
-            offset: vk::Offset2D { x: 0, y: 0 },
-            extent: swapchain.extent,
-        }];
-        // viewport creation info
-        let viewport_create_info = vk::PipelineViewportStateCreateInfo::builder()
-            .viewports(&viewports)
-            .scissors(&scissors);
+// the pipelines every Fae starts with
+pub(crate) fn default_pipelines() -> Vec<(String, PipelineBuilder)> {
+    vec![
+        (
+            WIREFRAME_PIPELINE.to_string(),
+            Pipelines::builder().polygon_mode(vk::PolygonMode::LINE),
+        ),
+        (SOLID_PIPELINE.to_string(), Pipelines::builder()),
+    ]
+}
 
-        // rasterizer creation info
-        let rasterizer_creation_info = vk::PipelineRasterizationStateCreateInfo::builder()
-            .line_width(1.0)
-            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
-            .cull_mode(vk::CullModeFlags::NONE)
-            .polygon_mode(vk::PolygonMode::LINE);
+struct NamedPipeline {
+    name: String,
+    pipeline: vk::Pipeline,
+    // kept to build the pipeline again after the device was lost
+    builder: PipelineBuilder,
+}
 
-        // multisampler creation info
-        let multisampler_create_info = vk::PipelineMultisampleStateCreateInfo::builder()
-            .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+// every graphics pipeline by name, they all share one layout
+pub struct Pipelines {
+    pub layout: vk::PipelineLayout,
+    pub descriptor_set_layouts: Vec<vk::DescriptorSetLayout>,
+    // in the order they were added
+    pipelines: Vec<NamedPipeline>,
+}
 
-        // color blending and transparency
-        let color_blend_attachments = [vk::PipelineColorBlendAttachmentState::builder()
-            .blend_enable(true)
-            .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
-            .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
-            .color_blend_op(vk::BlendOp::ADD)
-            .src_alpha_blend_factor(vk::BlendFactor::SRC_ALPHA)
-            .dst_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
-            .alpha_blend_op(vk::BlendOp::ADD)
-            .color_write_mask(
-                vk::ColorComponentFlags::R
-                    | vk::ColorComponentFlags::G
-                    | vk::ColorComponentFlags::B
-                    | vk::ColorComponentFlags::A,
-            )
-            .build()];
-        let color_blend_create_info =
-            vk::PipelineColorBlendStateCreateInfo::builder().attachments(&color_blend_attachments);
+impl Pipelines {
+    // filled triangles with the built in shaders, drawing Model<VertexData, InstanceData>
+    pub fn builder() -> PipelineBuilder {
+        PipelineBuilder {
+            vertex_shader: vk_shader_macros::include_glsl!("./shaders/shader.vert").to_vec(),
+            fragment_shader: vk_shader_macros::include_glsl!("./shaders/shader.frag").to_vec(),
+            shader_files: None,
+            vertex_input: Model::<VertexData, InstanceData>::vertex_input(),
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            polygon_mode: vk::PolygonMode::FILL,
+            cull_mode: vk::CullModeFlags::NONE,
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            depth_test: true,
+            depth_write: true,
+            depth_compare: vk::CompareOp::LESS_OR_EQUAL,
+            blend: BlendMode::Alpha,
+            dynamic_states: vec![],
+        }
+    }
 
+    pub fn init(
+        logical_device: &ash::Device,
+        render_pass: vk::RenderPass,
+        builders: &[(String, PipelineBuilder)],
+        debug: Option<&FaeDebug>,
+    ) -> Result<Pipelines, FaeError> {
         let descriptor_set_layout_binding_descs = [vk::DescriptorSetLayoutBinding::builder()
             .binding(0)
-            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            // the camera lives in the per frame uniform buffer, bound with a dynamic offset
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
             .descriptor_count(1)
             .stage_flags(vk::ShaderStageFlags::VERTEX)
             .build()];
@@ -253,54 +262,86 @@ impl Pipeline {
             .bindings(&descriptor_set_layout_binding_descs);
         let descriptor_set_layout = unsafe {
             logical_device.create_descriptor_set_layout(&descriptor_set_layout_info, None)
-        }?;
+        }
+        .context("creating the descriptor set layout")?;
         let desc_layouts = vec![descriptor_set_layout];
 
         // data to pass to pipeline not attached to verticies
         let pipeline_layout_info =
             vk::PipelineLayoutCreateInfo::builder().set_layouts(&desc_layouts);
         let pipeline_layout =
-            unsafe { logical_device.create_pipeline_layout(&pipeline_layout_info, None) }?;
-
-        let depth_stencil_info = vk::PipelineDepthStencilStateCreateInfo::builder()
-            .depth_test_enable(true)
-            .depth_write_enable(true)
-            .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL);
-
-        // pipeline creation info
-        let pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
-            .stages(&shader_stages)
-            .vertex_input_state(&vertex_input_create_info)
-            .input_assembly_state(&input_assembly_create_info)
-            .viewport_state(&viewport_create_info)
-            .rasterization_state(&rasterizer_creation_info)
-            .multisample_state(&multisampler_create_info)
-            .depth_stencil_state(&depth_stencil_info)
-            .color_blend_state(&color_blend_create_info)
-            .layout(pipeline_layout)
-            .render_pass(*render_pass)
-            .subpass(0);
-        let graphics_pipeline = unsafe {
-            logical_device
-                .create_graphics_pipelines(
-                    vk::PipelineCache::null(),
-                    &[pipeline_create_info.build()],
-                    None,
-                )
-                .expect("A problem occured with pipeline creation")
-        }[0];
+            unsafe { logical_device.create_pipeline_layout(&pipeline_layout_info, None) }
+                .context("creating the pipeline layout")?;
 
-        // cleanup shader modules they are no loger needed after the pipeline creation
-        unsafe {
-            logical_device.destroy_shader_module(fragment_shader_module, None);
-            logical_device.destroy_shader_module(vertex_shader_module, None);
+        if let Some(debug) = debug {
+            debug.set_object_name(logical_device, pipeline_layout, "pipeline layout");
+            debug.set_object_name(logical_device, descriptor_set_layout, "camera set layout");
         }
 
-        Ok(Pipeline {
-            pipeline: graphics_pipeline,
+        let mut pipelines = Pipelines {
             layout: pipeline_layout,
             descriptor_set_layouts: desc_layouts,
-        })
+            pipelines: vec![],
+        };
+        for (name, builder) in builders {
+            if let Err(e) =
+                pipelines.insert(logical_device, render_pass, name, builder.clone(), debug)
+            {
+                pipelines.cleanup(logical_device);
+                return Err(e);
+            }
+        }
+        Ok(pipelines)
+    }
+
+    pub fn get(&self, name: &str) -> Option<vk::Pipeline> {
+        self.pipelines
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| p.pipeline)
+    }
+
+    pub(crate) fn builders(&self) -> Vec<(String, PipelineBuilder)> {
+        self.pipelines
+            .iter()
+            .map(|p| (p.name.clone(), p.builder.clone()))
+            .collect()
+    }
+
+    // builds a pipeline and adds it under the name. A pipeline it replaces is returned, it has
+    // to be destroyed once no frame in flight uses it anymore.
+    pub fn insert(
+        &mut self,
+        logical_device: &ash::Device,
+        render_pass: vk::RenderPass,
+        name: &str,
+        builder: PipelineBuilder,
+        debug: Option<&FaeDebug>,
+    ) -> Result<Option<vk::Pipeline>, FaeError> {
+        let mut builder = builder;
+        // shader_files only sets the paths, the code is read the first time it is needed
+        if builder.vertex_shader.is_empty() {
+            builder.load_shaders()?;
+        }
+        let pipeline =
+            create_graphics_pipeline(logical_device, self.layout, render_pass, &builder)?;
+        if let Some(debug) = debug {
+            debug.set_object_name(logical_device, pipeline, &format!("{} pipeline", name));
+        }
+        match self.pipelines.iter_mut().find(|p| p.name == name) {
+            Some(existing) => {
+                existing.builder = builder;
+                Ok(Some(std::mem::replace(&mut existing.pipeline, pipeline)))
+            }
+            None => {
+                self.pipelines.push(NamedPipeline {
+                    name: name.to_string(),
+                    pipeline,
+                    builder,
+                });
+                Ok(None)
+            }
+        }
     }
 
     pub fn cleanup(&self, logical_device: &ash::Device) {
@@ -308,8 +349,154 @@ impl Pipeline {
             for dsl in &self.descriptor_set_layouts {
                 logical_device.destroy_descriptor_set_layout(*dsl, None);
             }
-            logical_device.destroy_pipeline(self.pipeline, None);
+            for named in &self.pipelines {
+                logical_device.destroy_pipeline(named.pipeline, None);
+            }
             logical_device.destroy_pipeline_layout(self.layout, None);
         }
     }
 }
+
+fn create_graphics_pipeline(
+    logical_device: &ash::Device,
+    pipeline_layout: vk::PipelineLayout,
+    render_pass: vk::RenderPass,
+    builder: &PipelineBuilder,
+) -> Result<vk::Pipeline, FaeError> {
+    // create vertex shader module
+    let vertex_shader_create_info =
+        vk::ShaderModuleCreateInfo::builder().code(&builder.vertex_shader);
+    let vertex_shader_module =
+        unsafe { logical_device.create_shader_module(&vertex_shader_create_info, None) }.map_err(
+            |result| FaeError::Shader {
+                step: "creating the vertex shader module",
+                message: result.to_string(),
+            },
+        )?;
+    // create fragment shader module
+    let fragment_shader_create_info =
+        vk::ShaderModuleCreateInfo::builder().code(&builder.fragment_shader);
+    let fragment_shader_module =
+        match unsafe { logical_device.create_shader_module(&fragment_shader_create_info, None) } {
+            Ok(module) => module,
+            Err(result) => {
+                unsafe { logical_device.destroy_shader_module(vertex_shader_module, None) };
+                return Err(FaeError::Shader {
+                    step: "creating the fragment shader module",
+                    message: result.to_string(),
+                });
+            }
+        };
+    // define what functiuon should be used as the entry point in the shader
+    let main_function_name = std::ffi::CString::new("main").unwrap();
+    // shader stage creation info
+    let vertex_shader_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+        .stage(vk::ShaderStageFlags::VERTEX)
+        .module(vertex_shader_module)
+        .name(&main_function_name);
+    let fragment_shader_stage_create_info = vk::PipelineShaderStageCreateInfo::builder()
+        .stage(vk::ShaderStageFlags::FRAGMENT)
+        .module(fragment_shader_module)
+        .name(&main_function_name);
+    // create shader stages
+    let shader_stages = vec![
+        vertex_shader_stage_create_info.build(),
+        fragment_shader_stage_create_info.build(),
+    ];
+
+    // shader input creation info
+    let vertex_input_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
+        .vertex_attribute_descriptions(&builder.vertex_input.attributes)
+        .vertex_binding_descriptions(&builder.vertex_input.bindings);
+    // is topology points or triangles
+    let input_assembly_create_info =
+        vk::PipelineInputAssemblyStateCreateInfo::builder().topology(builder.topology);
+    // viewport and scissor are set for every view while recording
+    let viewport_create_info = vk::PipelineViewportStateCreateInfo::builder()
+        .viewport_count(1)
+        .scissor_count(1);
+
+    // rasterizer creation info
+    let rasterizer_creation_info = vk::PipelineRasterizationStateCreateInfo::builder()
+        .line_width(1.0)
+        .front_face(builder.front_face)
+        .cull_mode(builder.cull_mode)
+        .polygon_mode(builder.polygon_mode);
+
+    // multisampler creation info
+    let multisampler_create_info = vk::PipelineMultisampleStateCreateInfo::builder()
+        .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+
+    // color blending and transparency
+    let (src_factor, dst_factor) = match builder.blend {
+        BlendMode::Opaque => (vk::BlendFactor::ONE, vk::BlendFactor::ZERO),
+        BlendMode::Alpha => (
+            vk::BlendFactor::SRC_ALPHA,
+            vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+        ),
+        BlendMode::Additive => (vk::BlendFactor::SRC_ALPHA, vk::BlendFactor::ONE),
+    };
+    let color_blend_attachments = [vk::PipelineColorBlendAttachmentState::builder()
+        .blend_enable(builder.blend != BlendMode::Opaque)
+        .src_color_blend_factor(src_factor)
+        .dst_color_blend_factor(dst_factor)
+        .color_blend_op(vk::BlendOp::ADD)
+        .src_alpha_blend_factor(src_factor)
+        .dst_alpha_blend_factor(dst_factor)
+        .alpha_blend_op(vk::BlendOp::ADD)
+        .color_write_mask(
+            vk::ColorComponentFlags::R
+                | vk::ColorComponentFlags::G
+                | vk::ColorComponentFlags::B
+                | vk::ColorComponentFlags::A,
+        )
+        .build()];
+    let color_blend_create_info =
+        vk::PipelineColorBlendStateCreateInfo::builder().attachments(&color_blend_attachments);
+
+    let depth_stencil_info = vk::PipelineDepthStencilStateCreateInfo::builder()
+        .depth_test_enable(builder.depth_test)
+        .depth_write_enable(builder.depth_write)
+        .depth_compare_op(builder.depth_compare);
+    let mut dynamic_states = vec![vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    for state in &builder.dynamic_states {
+        if !dynamic_states.contains(state) {
+            dynamic_states.push(*state);
+        }
+    }
+    let dynamic_state_info =
+        vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
+
+    // pipeline creation info
+    let pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
+        .stages(&shader_stages)
+        .vertex_input_state(&vertex_input_create_info)
+        .input_assembly_state(&input_assembly_create_info)
+        .viewport_state(&viewport_create_info)
+        .rasterization_state(&rasterizer_creation_info)
+        .multisample_state(&multisampler_create_info)
+        .depth_stencil_state(&depth_stencil_info)
+        .color_blend_state(&color_blend_create_info)
+        .dynamic_state(&dynamic_state_info)
+        .layout(pipeline_layout)
+        .render_pass(render_pass)
+        .subpass(0);
+    let graphics_pipelines = unsafe {
+        logical_device.create_graphics_pipelines(
+            vk::PipelineCache::null(),
+            &[pipeline_create_info.build()],
+            None,
+        )
+    };
+
+    // cleanup shader modules they are no loger needed after the pipeline creation
+    unsafe {
+        logical_device.destroy_shader_module(fragment_shader_module, None);
+        logical_device.destroy_shader_module(vertex_shader_module, None);
+    }
+
+    let graphics_pipelines = graphics_pipelines
+        .map_err(|(_, result)| result)
+        .context("creating the graphics pipeline")?;
+    Ok(graphics_pipelines[0])
+}
@@ -0,0 +1,427 @@
+use crate::debug::FaeDebug;
+use crate::error::{Context, FaeError};
+use ash::{version::DeviceV1_0, vk};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+// what has to be destroyed once a released resource is no longer used by the gpu
+pub(crate) enum Release {
+    Buffer(vk::Buffer, vk_mem::Allocation),
+    Image(vk::Image, vk::ImageView, vk_mem::Allocation),
+    Pipeline(vk::Pipeline),
+}
+
+// what a buffer or image is used for, the memory report groups allocations by this
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MemoryCategory {
+    Vertex,
+    Index,
+    // per frame buffers, filled again for every frame
+    Instance,
+    Uniform,
+    Staging,
+    Readback,
+    Depth,
+    RenderTarget,
+    Texture,
+    Other,
+}
+
+impl MemoryCategory {
+    pub const ALL: [MemoryCategory; 10] = [
+        MemoryCategory::Vertex,
+        MemoryCategory::Index,
+        MemoryCategory::Instance,
+        MemoryCategory::Uniform,
+        MemoryCategory::Staging,
+        MemoryCategory::Readback,
+        MemoryCategory::Depth,
+        MemoryCategory::RenderTarget,
+        MemoryCategory::Texture,
+        MemoryCategory::Other,
+    ];
+
+    // guessed from how a buffer is used, allocation sites that know better pass their category
+    // to Buffer::with_category
+    pub fn of_buffer(
+        usage: vk::BufferUsageFlags,
+        memory_usage: vk_mem::MemoryUsage,
+    ) -> MemoryCategory {
+        if memory_usage == vk_mem::MemoryUsage::GpuToCpu {
+            MemoryCategory::Readback
+        } else if usage.contains(vk::BufferUsageFlags::UNIFORM_BUFFER) {
+            MemoryCategory::Uniform
+        } else if usage.contains(vk::BufferUsageFlags::INDEX_BUFFER) {
+            MemoryCategory::Index
+        } else if usage.contains(vk::BufferUsageFlags::VERTEX_BUFFER) {
+            MemoryCategory::Vertex
+        } else if usage == vk::BufferUsageFlags::TRANSFER_SRC {
+            MemoryCategory::Staging
+        } else {
+            MemoryCategory::Other
+        }
+    }
+
+    pub fn of_image(usage: vk::ImageUsageFlags) -> MemoryCategory {
+        if usage.contains(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT) {
+            MemoryCategory::Depth
+        } else if usage.contains(vk::ImageUsageFlags::SAMPLED) {
+            MemoryCategory::Texture
+        } else if usage.contains(vk::ImageUsageFlags::COLOR_ATTACHMENT) {
+            MemoryCategory::RenderTarget
+        } else {
+            MemoryCategory::Other
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MemoryCategory::Vertex => "vertex",
+            MemoryCategory::Index => "index",
+            MemoryCategory::Instance => "instance",
+            MemoryCategory::Uniform => "uniform",
+            MemoryCategory::Staging => "staging",
+            MemoryCategory::Readback => "readback",
+            MemoryCategory::Depth => "depth",
+            MemoryCategory::RenderTarget => "render target",
+            MemoryCategory::Texture => "texture",
+            MemoryCategory::Other => "other",
+        }
+    }
+}
+
+struct Tracked {
+    category: MemoryCategory,
+    size_in_bytes: u64,
+    // only built in debug builds, for the leak report
+    description: Option<String>,
+}
+
+// the device context every Buffer and Image keeps a reference to. Dropped resources are not
+// destroyed right away but queued until the frames that may still use them have finished.
+pub struct FaeResources {
+    logical_device: ash::Device,
+    // destroyed in shutdown, after everything allocated from it
+    pub allocator: std::mem::ManuallyDrop<vk_mem::Allocator>,
+    // number of the frame currently being prepared
+    current_frame: Cell<u64>,
+    pending: RefCell<Vec<(u64, Release)>>,
+    shut_down: Cell<bool>,
+    next_id: Cell<u64>,
+    // every live resource by id
+    live: RefCell<std::collections::HashMap<u64, Tracked>>,
+    // names new buffers, None without debug utils
+    debug_utils: Option<ash::extensions::ext::DebugUtils>,
+}
+
+impl FaeResources {
+    pub fn new(
+        logical_device: &ash::Device,
+        allocator: vk_mem::Allocator,
+        debug: Option<&FaeDebug>,
+    ) -> Rc<FaeResources> {
+        Rc::new(FaeResources {
+            logical_device: logical_device.clone(),
+            allocator: std::mem::ManuallyDrop::new(allocator),
+            current_frame: Cell::new(0),
+            pending: RefCell::new(vec![]),
+            shut_down: Cell::new(false),
+            next_id: Cell::new(0),
+            live: RefCell::new(std::collections::HashMap::new()),
+            debug_utils: debug.map(|debug| debug.loader().clone()),
+        })
+    }
+
+    // does nothing without debug utils
+    pub(crate) fn set_object_name<H: vk::Handle>(&self, handle: H, name: &str) {
+        if let Some(loader) = &self.debug_utils {
+            crate::debug::set_object_name(loader, &self.logical_device, handle, name);
+        }
+    }
+
+    // hands out an id for a new resource, the description is only built in debug builds
+    pub(crate) fn track<F: FnOnce() -> String>(
+        &self,
+        category: MemoryCategory,
+        size_in_bytes: u64,
+        description: F,
+    ) -> u64 {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        let description = if cfg!(debug_assertions) {
+            Some(description())
+        } else {
+            None
+        };
+        self.live.borrow_mut().insert(
+            id,
+            Tracked {
+                category,
+                size_in_bytes,
+                description,
+            },
+        );
+        id
+    }
+
+    pub(crate) fn release(&self, id: u64, release: Release) {
+        self.live.borrow_mut().remove(&id);
+        self.defer(release);
+    }
+
+    // pipelines are not tracked, they are not allocated through vk-mem
+    pub(crate) fn release_pipeline(&self, pipeline: vk::Pipeline) {
+        self.defer(Release::Pipeline(pipeline));
+    }
+
+    fn defer(&self, release: Release) {
+        // after shutdown the allocator is gone, anything released now was leaked
+        if self.shut_down.get() {
+            return;
+        }
+        self.pending
+            .borrow_mut()
+            .push((self.current_frame.get(), release));
+    }
+
+    // call once per frame after waiting for its fence, completed_frames is the number of
+    // frames that are known to be finished. Destroys what the finished frames were using.
+    pub fn begin_frame(&self, frame_number: u64, completed_frames: u64) -> Result<(), FaeError> {
+        self.current_frame.set(frame_number);
+        let finished = take_finished(&mut self.pending.borrow_mut(), completed_frames);
+        for release in finished {
+            self.destroy(release)
+                .context("destroying released resources")?;
+        }
+        Ok(())
+    }
+
+    fn destroy(&self, release: Release) -> Result<(), vk_mem::error::Error> {
+        match release {
+            Release::Buffer(buffer, allocation) => {
+                self.allocator.destroy_buffer(buffer, &allocation)?;
+            }
+            Release::Image(image, view, allocation) => {
+                unsafe { self.logical_device.destroy_image_view(view, None) };
+                self.allocator.destroy_image(image, &allocation)?;
+            }
+            Release::Pipeline(pipeline) => unsafe {
+                self.logical_device.destroy_pipeline(pipeline, None)
+            },
+        }
+        Ok(())
+    }
+
+    // descriptions of resources that are still alive in the order they were created. Only debug
+    // builds keep descriptions, so in release builds this is empty even with live resources.
+    pub fn live_resources(&self) -> Vec<String> {
+        let mut live: Vec<(u64, String)> = self
+            .live
+            .borrow()
+            .iter()
+            .filter_map(|(id, tracked)| Some((*id, tracked.description.clone()?)))
+            .collect();
+        live.sort();
+        live.into_iter()
+            .map(|(_, description)| description)
+            .collect()
+    }
+
+    // number and total size of the live resources in every category, ordered by category.
+    // Categories without resources are listed with zeros so reports always have the same shape.
+    pub fn usage_by_category(&self) -> Vec<(MemoryCategory, usize, u64)> {
+        let mut usage: std::collections::BTreeMap<MemoryCategory, (usize, u64)> =
+            MemoryCategory::ALL.iter().map(|&c| (c, (0, 0))).collect();
+        for tracked in self.live.borrow().values() {
+            let (count, bytes) = usage.entry(tracked.category).or_insert((0, 0));
+            *count += 1;
+            *bytes += tracked.size_in_bytes;
+        }
+        usage
+            .into_iter()
+            .map(|(category, (count, bytes))| (category, count, bytes))
+            .collect()
+    }
+
+    // destroys everything that was released and then the allocator, the device has to be
+    // idle. Resources that are still alive are reported and leaked together with the allocator.
+    pub fn shutdown(resources: &mut Rc<FaeResources>) {
+        let pending: Vec<(u64, Release)> = resources.pending.borrow_mut().drain(..).collect();
+        for (_, release) in pending {
+            if let Err(e) = resources.destroy(release) {
+                log::error!("destroying a released resource failed: {}", e);
+            }
+        }
+        resources.shut_down.set(true);
+        for leak in resources.live_resources() {
+            log::error!("{} was never released", leak);
+        }
+        match Rc::get_mut(resources) {
+            Some(resources) => unsafe { std::mem::ManuallyDrop::drop(&mut resources.allocator) },
+            None => log::error!("resources are still referenced, leaking the allocator"),
+        }
+    }
+}
+
+pub struct Image {
+    pub image: vk::Image,
+    pub view: vk::ImageView,
+    allocation: vk_mem::Allocation,
+    resources: Rc<FaeResources>,
+    id: u64,
+}
+
+impl Image {
+    // a 2d image with one mip level in device local memory and a view of the whole image
+    pub fn new(
+        resources: &Rc<FaeResources>,
+        extent: vk::Extent2D,
+        format: vk::Format,
+        usage: vk::ImageUsageFlags,
+        aspect_mask: vk::ImageAspectFlags,
+    ) -> Result<Image, FaeError> {
+        let extent3d = vk::Extent3D {
+            width: extent.width,
+            height: extent.height,
+            depth: 1,
+        };
+        let image_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(format)
+            .extent(extent3d)
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+        let allocation_info = vk_mem::AllocationCreateInfo {
+            usage: vk_mem::MemoryUsage::GpuOnly,
+            ..Default::default()
+        };
+        let (image, allocation, allocation_info) = resources
+            .allocator
+            .create_image(&image_info, &allocation_info)
+            .context("allocating an image")?;
+
+        let subresource_range = vk::ImageSubresourceRange::builder()
+            .aspect_mask(aspect_mask)
+            .base_mip_level(0)
+            .level_count(1)
+            .base_array_layer(0)
+            .layer_count(1);
+        let image_view_create_info = vk::ImageViewCreateInfo::builder()
+            .image(image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(format)
+            .subresource_range(*subresource_range);
+        let view = match unsafe {
+            resources
+                .logical_device
+                .create_image_view(&image_view_create_info, None)
+        } {
+            Ok(view) => view,
+            Err(result) => {
+                resources
+                    .allocator
+                    .destroy_image(image, &allocation)
+                    .context("destroying an image")?;
+                return Err(FaeError::Vulkan {
+                    step: "creating an image view",
+                    result,
+                });
+            }
+        };
+        let size_in_bytes = allocation_info.get_size() as u64;
+        let id = resources.track(MemoryCategory::of_image(usage), size_in_bytes, || {
+            format!(
+                "{:?} image {}x{} ({:?})",
+                format, extent.width, extent.height, usage
+            )
+        });
+        Ok(Image {
+            image,
+            view,
+            allocation,
+            resources: resources.clone(),
+            id,
+        })
+    }
+}
+
+impl Drop for Image {
+    fn drop(&mut self) {
+        self.resources.release(
+            self.id,
+            Release::Image(self.image, self.view, self.allocation),
+        );
+    }
+}
+
+// removes and returns what was last used by one of the completed frames, in release order
+fn take_finished<T>(pending: &mut Vec<(u64, T)>, completed_frames: u64) -> Vec<T> {
+    let (finished, still_pending): (Vec<_>, Vec<_>) = pending
+        .drain(..)
+        .partition(|(last_use, _)| *last_use < completed_frames);
+    *pending = still_pending;
+    finished.into_iter().map(|(_, release)| release).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // what FrameSync::completed_frames reports with two frames in flight
+    fn completed_frames(frame_number: u64) -> u64 {
+        (frame_number + 1).saturating_sub(2)
+    }
+
+    #[test]
+    fn released_resources_wait_for_their_frame() {
+        // released while frames 5 and 6 were prepared
+        let mut pending = vec![(5, "a"), (6, "b"), (5, "c")];
+        // frame 5 may still be in flight while frame 6 is prepared
+        assert!(take_finished(&mut pending, completed_frames(6)).is_empty());
+        assert_eq!(
+            take_finished(&mut pending, completed_frames(7)),
+            vec!["a", "c"]
+        );
+        assert_eq!(pending, vec![(6, "b")]);
+        assert_eq!(take_finished(&mut pending, completed_frames(8)), vec!["b"]);
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn nothing_finishes_before_the_first_frames() {
+        let mut pending = vec![(0, "a")];
+        assert!(take_finished(&mut pending, completed_frames(0)).is_empty());
+        assert!(take_finished(&mut pending, completed_frames(1)).is_empty());
+        assert_eq!(take_finished(&mut pending, completed_frames(2)), vec!["a"]);
+    }
+
+    #[test]
+    fn buffer_categories() {
+        let category = MemoryCategory::of_buffer;
+        let gpu = vk_mem::MemoryUsage::GpuOnly;
+        let upload = vk_mem::MemoryUsage::CpuToGpu;
+        assert_eq!(
+            category(vk::BufferUsageFlags::VERTEX_BUFFER, gpu),
+            MemoryCategory::Vertex
+        );
+        assert_eq!(
+            category(vk::BufferUsageFlags::UNIFORM_BUFFER, upload),
+            MemoryCategory::Uniform
+        );
+        assert_eq!(
+            category(vk::BufferUsageFlags::TRANSFER_SRC, upload),
+            MemoryCategory::Staging
+        );
+        assert_eq!(
+            category(
+                vk::BufferUsageFlags::TRANSFER_DST,
+                vk_mem::MemoryUsage::GpuToCpu
+            ),
+            MemoryCategory::Readback
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use crate::error::FaeError;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+// the directories are read at most this often, so reload_shaders can be called every frame
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+}
+
+// spir-v code from a file: .spv files are used as they are, anything else is compiled as glsl
+// for the given stage. #include is resolved relative to the including file.
+pub fn load_shader<P: AsRef<Path>>(path: P, stage: ShaderStage) -> Result<Vec<u32>, FaeError> {
+    let path = path.as_ref();
+    if path
+        .extension()
+        .map_or(false, |extension| extension == "spv")
+    {
+        let mut file = std::fs::File::open(path).map_err(FaeError::asset(path))?;
+        return ash::util::read_spv(&mut file).map_err(FaeError::asset(path));
+    }
+    let source = std::fs::read_to_string(path).map_err(FaeError::asset(path))?;
+    let (step, kind) = match stage {
+        ShaderStage::Vertex => ("compiling the vertex shader", shaderc::ShaderKind::Vertex),
+        ShaderStage::Fragment => (
+            "compiling the fragment shader",
+            shaderc::ShaderKind::Fragment,
+        ),
+    };
+    let error = |message: String| FaeError::Shader { step, message };
+    let mut compiler =
+        shaderc::Compiler::new().ok_or_else(|| error("no shader compiler".to_string()))?;
+    let mut options =
+        shaderc::CompileOptions::new().ok_or_else(|| error("no compile options".to_string()))?;
+    let directory = parent_directory(path);
+    options.set_include_callback(move |name, include_type, including, _depth| {
+        let file = match include_type {
+            shaderc::IncludeType::Relative => parent_directory(Path::new(including)).join(name),
+            shaderc::IncludeType::Standard => directory.join(name),
+        };
+        let content =
+            std::fs::read_to_string(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+        Ok(shaderc::ResolvedInclude {
+            resolved_name: file.to_string_lossy().into_owned(),
+            content,
+        })
+    });
+    // the compiler messages already name the file and line
+    let artifact = compiler
+        .compile_into_spirv(
+            &source,
+            kind,
+            &path.to_string_lossy(),
+            "main",
+            Some(&options),
+        )
+        .map_err(|e| error(e.to_string()))?;
+    Ok(artifact.as_binary().to_vec())
+}
+
+// "." for a bare file name, so it can be read and watched
+pub(crate) fn parent_directory(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+// polls the modification times of every file in the directories shaders are loaded from,
+// so a change to an included file is noticed as well
+#[derive(Default)]
+pub(crate) struct ShaderWatcher {
+    directories: HashMap<PathBuf, HashMap<PathBuf, SystemTime>>,
+    last_poll: Option<Instant>,
+}
+
+impl ShaderWatcher {
+    // the directories where a file was added, changed or removed since the last call. A
+    // directory seen for the first time is only remembered.
+    pub(crate) fn changed_directories(&mut self, directories: &[PathBuf]) -> Vec<PathBuf> {
+        if self
+            .last_poll
+            .map_or(false, |last| last.elapsed() < POLL_INTERVAL)
+        {
+            return vec![];
+        }
+        self.last_poll = Some(Instant::now());
+        let mut changed = vec![];
+        for directory in directories {
+            let files = modification_times(directory);
+            match self.directories.insert(directory.clone(), files) {
+                Some(before) if before != self.directories[directory] => {
+                    changed.push(directory.clone())
+                }
+                _ => {}
+            }
+        }
+        changed
+    }
+}
+
+// an unreadable directory counts as empty, it shows up as a change once it can be read again
+fn modification_times(directory: &Path) -> HashMap<PathBuf, SystemTime> {
+    let entries = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(_) => return HashMap::new(),
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+            Some((entry.path(), modified))
+        })
+        .collect()
+}
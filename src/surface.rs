@@ -1,5 +1,5 @@
 use ash::vk;
-use winapi::{shared::windef::HWND, um::libloaderapi::GetModuleHandleW};
+use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
 
 pub struct FaeSurface {
     pub surface: vk::SurfaceKHR,
@@ -7,27 +7,107 @@ pub struct FaeSurface {
 }
 
 impl FaeSurface {
+    // instance extensions needed to create a surface for this window
+    pub fn required_extension_names(
+        window: &winit::window::Window,
+    ) -> Result<Vec<&'static std::ffi::CStr>, vk::Result> {
+        let platform_extension_name = match window.raw_window_handle() {
+            #[cfg(target_os = "windows")]
+            RawWindowHandle::Windows(_) => ash::extensions::khr::Win32Surface::name(),
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            RawWindowHandle::Xlib(_) => ash::extensions::khr::XlibSurface::name(),
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            RawWindowHandle::Xcb(_) => ash::extensions::khr::XcbSurface::name(),
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            RawWindowHandle::Wayland(_) => ash::extensions::khr::WaylandSurface::name(),
+            _ => return Err(vk::Result::ERROR_EXTENSION_NOT_PRESENT),
+        };
+        Ok(vec![
+            ash::extensions::khr::Surface::name(),
+            platform_extension_name,
+        ])
+    }
+
     pub fn init(
         window: &winit::window::Window,
         entry: &ash::Entry,
         instance: &ash::Instance,
     ) -> Result<FaeSurface, vk::Result> {
-        // TODO: make cross platform is currently windows only
-        // TODO: use conditional compilation
-        // create window surface
-        use winit::platform::windows::WindowExtWindows;
-        let hwnd = window.hwnd() as HWND;
-        let h_instance = unsafe { GetModuleHandleW(std::ptr::null()) as *const std::ffi::c_void };
-        let win32_create_info = vk::Win32SurfaceCreateInfoKHR {
-            s_type: vk::StructureType::WIN32_SURFACE_CREATE_INFO_KHR,
-            p_next: std::ptr::null(),
-            flags: Default::default(),
-            hinstance: h_instance,
-            hwnd: hwnd as *const std::ffi::c_void,
+        // create window surface for whichever windowing system winit picked
+        let surface = match window.raw_window_handle() {
+            #[cfg(target_os = "windows")]
+            RawWindowHandle::Windows(handle) => {
+                let win32_create_info = vk::Win32SurfaceCreateInfoKHR::builder()
+                    .hinstance(handle.hinstance as *const std::ffi::c_void)
+                    .hwnd(handle.hwnd as *const std::ffi::c_void);
+                let win32_surface_loader = ash::extensions::khr::Win32Surface::new(entry, instance);
+                unsafe { win32_surface_loader.create_win32_surface(&win32_create_info, None) }?
+            }
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            RawWindowHandle::Xlib(handle) => {
+                let xlib_create_info = vk::XlibSurfaceCreateInfoKHR::builder()
+                    .dpy(handle.display as *mut vk::Display)
+                    .window(handle.window as vk::Window);
+                let xlib_surface_loader = ash::extensions::khr::XlibSurface::new(entry, instance);
+                unsafe { xlib_surface_loader.create_xlib_surface(&xlib_create_info, None) }?
+            }
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            RawWindowHandle::Xcb(handle) => {
+                let xcb_create_info = vk::XcbSurfaceCreateInfoKHR::builder()
+                    .connection(handle.connection as *mut vk::xcb_connection_t)
+                    .window(handle.window as vk::xcb_window_t);
+                let xcb_surface_loader = ash::extensions::khr::XcbSurface::new(entry, instance);
+                unsafe { xcb_surface_loader.create_xcb_surface(&xcb_create_info, None) }?
+            }
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            RawWindowHandle::Wayland(handle) => {
+                let wayland_create_info = vk::WaylandSurfaceCreateInfoKHR::builder()
+                    .display(handle.display as *mut vk::wl_display)
+                    .surface(handle.surface as *mut vk::wl_surface);
+                let wayland_surface_loader =
+                    ash::extensions::khr::WaylandSurface::new(entry, instance);
+                unsafe {
+                    wayland_surface_loader.create_wayland_surface(&wayland_create_info, None)
+                }?
+            }
+            _ => return Err(vk::Result::ERROR_EXTENSION_NOT_PRESENT),
         };
-        let win32_surface_loader = ash::extensions::khr::Win32Surface::new(entry, instance);
-        let surface =
-            unsafe { win32_surface_loader.create_win32_surface(&win32_create_info, None) }?;
         let surface_loader = ash::extensions::khr::Surface::new(entry, instance);
         Ok(FaeSurface {
             surface,
@@ -1,201 +1,414 @@
+use crate::config::SwapchainConfig;
+use crate::debug::FaeDebug;
+use crate::error::{Context, FaeError};
 use crate::instance_device_queues::QueueFamilies;
+use crate::resources::{FaeResources, Image};
 use crate::surface::FaeSurface;
 use ash::{version::DeviceV1_0, vk};
+use std::rc::Rc;
 
 pub struct FaeSwapchain {
     pub swapchain_loader: ash::extensions::khr::Swapchain,
     pub swapchain: vk::SwapchainKHR,
-    _images: Vec<vk::Image>,
+    pub images: Vec<vk::Image>,
     image_views: Vec<vk::ImageView>,
-    depth_image: vk::Image,
-    _depth_image_allocation: vk_mem::Allocation,
-    _depth_image_allocation_info: vk_mem::AllocationInfo,
-    depth_image_view: vk::ImageView,
+    depth: Image,
     pub framebuffers: Vec<vk::Framebuffer>,
     pub surface_format: vk::SurfaceFormatKHR,
+    pub present_mode: vk::PresentModeKHR,
+    // TRANSFER_SRC is only there if the surface supports it, see Fae::request_capture
+    pub image_usage: vk::ImageUsageFlags,
     pub extent: vk::Extent2D,
-    pub image_available: Vec<vk::Semaphore>,
-    pub rendering_finished: Vec<vk::Semaphore>,
-    pub may_begin_drawing: Vec<vk::Fence>,
     pub amount_of_images: u32,
-    pub current_image: usize,
+    config: SwapchainConfig,
+}
+
+// everything that depends on the size of the surface and has to be rebuilt on resize
+struct SwapchainResources {
+    swapchain: vk::SwapchainKHR,
+    images: Vec<vk::Image>,
+    image_views: Vec<vk::ImageView>,
+    depth: Image,
+    surface_format: vk::SurfaceFormatKHR,
+    present_mode: vk::PresentModeKHR,
+    image_usage: vk::ImageUsageFlags,
+    extent: vk::Extent2D,
 }
 
 impl FaeSwapchain {
+    #[allow(clippy::too_many_arguments)]
     pub fn init(
         instance: &ash::Instance,
         physical_device: vk::PhysicalDevice,
         logical_device: &ash::Device,
         surfaces: &FaeSurface,
         q_families: &QueueFamilies,
-        allocator: &vk_mem::Allocator,
-    ) -> Result<FaeSwapchain, vk::Result> {
-        // query surface information
-        let surface_capabilites = surfaces.get_capabilities(physical_device)?;
-        let extent = surface_capabilites.current_extent;
-        let _surface_present_modes = surfaces.get_present_modes(physical_device)?;
-        let surface_format = *surfaces.get_formats(physical_device)?.first().unwrap();
-        let queue_families = [q_families.graphics_q_index.unwrap()];
-        let swapchain_create_info = vk::SwapchainCreateInfoKHR::builder()
-            .surface(surfaces.surface)
-            .min_image_count(
-                3.max(surface_capabilites.min_image_count)
-                    .min(surface_capabilites.max_image_count),
-            )
-            .image_format(surface_format.format)
-            .image_color_space(surface_format.color_space)
-            .image_extent(extent)
-            .image_array_layers(1)
-            .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
-            .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
-            .queue_family_indices(&queue_families)
-            .pre_transform(surface_capabilites.current_transform)
-            .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
-            .present_mode(vk::PresentModeKHR::FIFO);
+        resources: &Rc<FaeResources>,
+        window_extent: vk::Extent2D,
+        config: &SwapchainConfig,
+        debug: Option<&FaeDebug>,
+    ) -> Result<FaeSwapchain, FaeError> {
         let swapchain_loader = ash::extensions::khr::Swapchain::new(instance, logical_device);
-        let swapchain = unsafe { swapchain_loader.create_swapchain(&swapchain_create_info, None)? };
-
-        // get Vec of vkImages
-        let swapchain_images = unsafe { swapchain_loader.get_swapchain_images(swapchain)? };
-        let amount_of_images = swapchain_images.len() as u32;
-        //create ImageViews
-        let mut swapchain_image_views = Vec::with_capacity(swapchain_images.len());
-        for image in &swapchain_images {
-            let subresource_range = vk::ImageSubresourceRange::builder()
-                .aspect_mask(vk::ImageAspectFlags::COLOR)
-                .base_mip_level(0)
-                .level_count(1)
-                .base_array_layer(0)
-                .layer_count(1);
-            let image_view_create_info = vk::ImageViewCreateInfo::builder()
-                .image(*image)
-                .view_type(vk::ImageViewType::TYPE_2D)
-                .format(vk::Format::B8G8R8A8_UNORM)
-                .subresource_range(*subresource_range);
-            let image_view =
-                unsafe { logical_device.create_image_view(&image_view_create_info, None) }?;
-            swapchain_image_views.push(image_view);
-        }
+        let created = create_resources(
+            &swapchain_loader,
+            physical_device,
+            logical_device,
+            surfaces,
+            q_families,
+            resources,
+            window_extent,
+            config,
+            vk::SwapchainKHR::null(),
+        )?;
+        let amount_of_images = created.images.len() as u32;
 
-        let extent3d = vk::Extent3D {
-            width: extent.width,
-            height: extent.height,
-            depth: 1,
-        };
-        let depth_image_info = vk::ImageCreateInfo::builder()
-            .image_type(vk::ImageType::TYPE_2D)
-            .format(vk::Format::D32_SFLOAT)
-            .extent(extent3d)
-            .mip_levels(1)
-            .array_layers(1)
-            .samples(vk::SampleCountFlags::TYPE_1)
-            .tiling(vk::ImageTiling::OPTIMAL)
-            .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)
-            .sharing_mode(vk::SharingMode::EXCLUSIVE)
-            .queue_family_indices(&queue_families);
-        let allocation_info = vk_mem::AllocationCreateInfo {
-            usage: vk_mem::MemoryUsage::GpuOnly,
-            ..Default::default()
+        let fae_swapchain = FaeSwapchain {
+            swapchain_loader,
+            swapchain: created.swapchain,
+            images: created.images,
+            image_views: created.image_views,
+            depth: created.depth,
+            framebuffers: vec![],
+            surface_format: created.surface_format,
+            present_mode: created.present_mode,
+            image_usage: created.image_usage,
+            extent: created.extent,
+            amount_of_images,
+            config: config.clone(),
         };
-        let (depth_image, _depth_image_allocation, _depth_image_allocation_info) = allocator
-            .create_image(&depth_image_info, &allocation_info)
-            .unwrap();
+        if let Some(debug) = debug {
+            fae_swapchain.set_debug_names(logical_device, debug);
+        }
+        Ok(fae_swapchain)
+    }
 
-        let subresource_range = vk::ImageSubresourceRange::builder()
-            .aspect_mask(vk::ImageAspectFlags::DEPTH)
-            .base_mip_level(0)
-            .level_count(1)
-            .base_array_layer(0)
-            .layer_count(1);
-        let image_view_create_info = vk::ImageViewCreateInfo::builder()
-            .image(depth_image)
-            .view_type(vk::ImageViewType::TYPE_2D)
-            .format(vk::Format::D32_SFLOAT)
-            .subresource_range(*subresource_range);
-        let depth_image_view =
-            unsafe { logical_device.create_image_view(&image_view_create_info, None) }?;
-
-        // use semaphores for syncing image views
-        let mut image_available = vec![];
-        let mut rendering_finished = vec![];
-        let mut may_begin_drawing = vec![];
-        let semaphore_create_info = vk::SemaphoreCreateInfo::builder();
-        // use fence to sync cpu and gpu
-        // set fence to signaled state
-        let fence_create_info =
-            vk::FenceCreateInfo::builder().flags(vk::FenceCreateFlags::SIGNALED);
-        // sync each image
-        for _ in 0..amount_of_images {
-            let semaphore_available =
-                unsafe { logical_device.create_semaphore(&semaphore_create_info, None) }?;
-            let semaphore_finished =
-                unsafe { logical_device.create_semaphore(&semaphore_create_info, None) }?;
-            image_available.push(semaphore_available);
-            rendering_finished.push(semaphore_finished);
-            let fence = unsafe { logical_device.create_fence(&fence_create_info, None) }?;
-            may_begin_drawing.push(fence);
+    // rebuilds the swapchain, its depth image and framebuffers for the current surface size,
+    // the device has to be idle before calling this
+    #[allow(clippy::too_many_arguments)]
+    pub fn recreate(
+        &mut self,
+        physical_device: vk::PhysicalDevice,
+        logical_device: &ash::Device,
+        surfaces: &FaeSurface,
+        q_families: &QueueFamilies,
+        resources: &Rc<FaeResources>,
+        window_extent: vk::Extent2D,
+        render_pass: vk::RenderPass,
+        debug: Option<&FaeDebug>,
+    ) -> Result<(), FaeError> {
+        unsafe { self.destroy_size_dependent(logical_device) };
+        // hand the old swapchain over so the driver can reuse its resources
+        let old_swapchain = self.swapchain;
+        let created = create_resources(
+            &self.swapchain_loader,
+            physical_device,
+            logical_device,
+            surfaces,
+            q_families,
+            resources,
+            window_extent,
+            &self.config,
+            old_swapchain,
+        );
+        unsafe { self.swapchain_loader.destroy_swapchain(old_swapchain, None) };
+        self.swapchain = vk::SwapchainKHR::null();
+        let created = created?;
+
+        self.amount_of_images = created.images.len() as u32;
+        self.swapchain = created.swapchain;
+        self.images = created.images;
+        self.image_views = created.image_views;
+        // the old depth image is released here
+        self.depth = created.depth;
+        self.surface_format = created.surface_format;
+        self.present_mode = created.present_mode;
+        self.image_usage = created.image_usage;
+        self.extent = created.extent;
+        self.create_framebuffers(logical_device, render_pass)?;
+        if let Some(debug) = debug {
+            self.set_debug_names(logical_device, debug);
         }
+        Ok(())
+    }
 
-        Ok(FaeSwapchain {
-            swapchain_loader,
-            swapchain,
-            _images: swapchain_images,
-            image_views: swapchain_image_views,
-            depth_image,
-            _depth_image_allocation,
-            _depth_image_allocation_info,
-            depth_image_view,
-            framebuffers: vec![],
-            surface_format,
-            extent,
-            amount_of_images,
-            current_image: 0,
-            image_available,
-            rendering_finished,
-            may_begin_drawing,
-        })
+    fn set_debug_names(&self, logical_device: &ash::Device, debug: &FaeDebug) {
+        debug.set_object_name(logical_device, self.swapchain, "swapchain");
+        for (i, (image, view)) in self.images.iter().zip(&self.image_views).enumerate() {
+            debug.set_object_name(logical_device, *image, &format!("swapchain image {}", i));
+            debug.set_object_name(
+                logical_device,
+                *view,
+                &format!("swapchain image view {}", i),
+            );
+        }
+        debug.set_object_name(logical_device, self.depth.image, "depth image");
+        debug.set_object_name(logical_device, self.depth.view, "depth image view");
     }
 
     pub fn create_framebuffers(
         &mut self,
         logical_device: &ash::Device,
         render_pass: vk::RenderPass,
-    ) -> Result<(), vk::Result> {
+    ) -> Result<(), FaeError> {
         for iv in &self.image_views {
-            let i_view = [*iv, self.depth_image_view];
+            let i_view = [*iv, self.depth.view];
             let framebuffer_info = vk::FramebufferCreateInfo::builder()
                 .render_pass(render_pass)
                 .attachments(&i_view)
                 .width(self.extent.width)
                 .height(self.extent.height)
                 .layers(1);
-            let fb = unsafe { logical_device.create_framebuffer(&framebuffer_info, None) }?;
+            let fb = unsafe { logical_device.create_framebuffer(&framebuffer_info, None) }
+                .context("creating the swapchain framebuffers")?;
             self.framebuffers.push(fb);
         }
         Ok(())
     }
 
-    pub unsafe fn cleanup(&mut self, logical_device: &ash::Device, allocator: &vk_mem::Allocator) {
-        logical_device.destroy_image_view(self.depth_image_view, None);
-        allocator
-            .destroy_image(self.depth_image, &self._depth_image_allocation)
-            .unwrap();
-        for fence in &self.may_begin_drawing {
-            logical_device.destroy_fence(*fence, None);
-        }
-        for semaphore in &self.image_available {
-            logical_device.destroy_semaphore(*semaphore, None);
-        }
-        for semaphore in &self.rendering_finished {
-            logical_device.destroy_semaphore(*semaphore, None);
-        }
-        for fb in &self.framebuffers {
-            logical_device.destroy_framebuffer(*fb, None);
+    // destroys framebuffers and image views but not the swapchain itself, the depth image is
+    // released when it is replaced or the swapchain is dropped
+    unsafe fn destroy_size_dependent(&mut self, logical_device: &ash::Device) {
+        for fb in self.framebuffers.drain(..) {
+            logical_device.destroy_framebuffer(fb, None);
         }
-        for iv in &self.image_views {
-            logical_device.destroy_image_view(*iv, None);
+        for iv in self.image_views.drain(..) {
+            logical_device.destroy_image_view(iv, None);
         }
+    }
+
+    pub unsafe fn cleanup(&mut self, logical_device: &ash::Device) {
+        self.destroy_size_dependent(logical_device);
         self.swapchain_loader
             .destroy_swapchain(self.swapchain, None)
     }
 }
+
+#[allow(clippy::too_many_arguments)]
+fn create_resources(
+    swapchain_loader: &ash::extensions::khr::Swapchain,
+    physical_device: vk::PhysicalDevice,
+    logical_device: &ash::Device,
+    surfaces: &FaeSurface,
+    q_families: &QueueFamilies,
+    resources: &Rc<FaeResources>,
+    window_extent: vk::Extent2D,
+    config: &SwapchainConfig,
+    old_swapchain: vk::SwapchainKHR,
+) -> Result<SwapchainResources, FaeError> {
+    // query surface information
+    let surface_capabilites = surfaces
+        .get_capabilities(physical_device)
+        .map_err(FaeError::surface("querying the surface capabilities"))?;
+    // current_extent is u32::MAX when the surface size is decided by the swapchain (wayland)
+    let extent = if surface_capabilites.current_extent.width == std::u32::MAX {
+        vk::Extent2D {
+            width: window_extent
+                .width
+                .max(surface_capabilites.min_image_extent.width)
+                .min(surface_capabilites.max_image_extent.width),
+            height: window_extent
+                .height
+                .max(surface_capabilites.min_image_extent.height)
+                .min(surface_capabilites.max_image_extent.height),
+        }
+    } else {
+        surface_capabilites.current_extent
+    };
+    let present_mode = choose_present_mode(
+        &surfaces
+            .get_present_modes(physical_device)
+            .map_err(FaeError::surface("querying the present modes"))?,
+        &config.present_modes,
+    );
+    let surface_format = choose_surface_format(
+        &surfaces
+            .get_formats(physical_device)
+            .map_err(FaeError::surface("querying the surface formats"))?,
+        &config.surface_formats,
+    )
+    .ok_or(FaeError::Surface {
+        step: "choosing a surface format",
+        result: vk::Result::ERROR_FORMAT_NOT_SUPPORTED,
+    })?;
+    let queue_families = [q_families.graphics_q_index];
+    // allow copying out of the swapchain images for screenshots if the surface supports it
+    let image_usage = vk::ImageUsageFlags::COLOR_ATTACHMENT
+        | (surface_capabilites.supported_usage_flags & vk::ImageUsageFlags::TRANSFER_SRC);
+    // max_image_count of 0 means there is no upper limit
+    let max_image_count = if surface_capabilites.max_image_count == 0 {
+        std::u32::MAX
+    } else {
+        surface_capabilites.max_image_count
+    };
+    let swapchain_create_info = vk::SwapchainCreateInfoKHR::builder()
+        .surface(surfaces.surface)
+        .min_image_count(
+            3.max(surface_capabilites.min_image_count)
+                .min(max_image_count),
+        )
+        .image_format(surface_format.format)
+        .image_color_space(surface_format.color_space)
+        .image_extent(extent)
+        .image_array_layers(1)
+        .image_usage(image_usage)
+        .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
+        .queue_family_indices(&queue_families)
+        .pre_transform(surface_capabilites.current_transform)
+        .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
+        .present_mode(present_mode)
+        .old_swapchain(old_swapchain);
+    let swapchain = unsafe { swapchain_loader.create_swapchain(&swapchain_create_info, None) }
+        .map_err(FaeError::surface("creating the swapchain"))?;
+
+    // get Vec of vkImages
+    let swapchain_images = unsafe { swapchain_loader.get_swapchain_images(swapchain) }
+        .context("getting the swapchain images")?;
+    //create ImageViews
+    let mut swapchain_image_views = Vec::with_capacity(swapchain_images.len());
+    for image in &swapchain_images {
+        let subresource_range = vk::ImageSubresourceRange::builder()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .base_mip_level(0)
+            .level_count(1)
+            .base_array_layer(0)
+            .layer_count(1);
+        let image_view_create_info = vk::ImageViewCreateInfo::builder()
+            .image(*image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(surface_format.format)
+            .subresource_range(*subresource_range);
+        let image_view = unsafe { logical_device.create_image_view(&image_view_create_info, None) }
+            .context("creating the swapchain image views")?;
+        swapchain_image_views.push(image_view);
+    }
+
+    let depth = Image::new(
+        resources,
+        extent,
+        vk::Format::D32_SFLOAT,
+        vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+        vk::ImageAspectFlags::DEPTH,
+    )?;
+
+    Ok(SwapchainResources {
+        swapchain,
+        images: swapchain_images,
+        image_views: swapchain_image_views,
+        depth,
+        surface_format,
+        present_mode,
+        image_usage,
+        extent,
+    })
+}
+
+// first preferred mode the surface supports, FIFO has to be supported by every surface
+fn choose_present_mode(
+    available: &[vk::PresentModeKHR],
+    preferred: &[vk::PresentModeKHR],
+) -> vk::PresentModeKHR {
+    preferred
+        .iter()
+        .find(|mode| available.contains(mode))
+        .copied()
+        .unwrap_or(vk::PresentModeKHR::FIFO)
+}
+
+// exact match first, then a preferred format in any color space, then whatever the surface
+// lists first. None only if the surface reports no formats at all.
+fn choose_surface_format(
+    available: &[vk::SurfaceFormatKHR],
+    preferred: &[vk::SurfaceFormatKHR],
+) -> Option<vk::SurfaceFormatKHR> {
+    // a single UNDEFINED entry means the surface takes any format
+    if available.len() == 1 && available[0].format == vk::Format::UNDEFINED {
+        return Some(preferred.first().copied().unwrap_or(vk::SurfaceFormatKHR {
+            format: vk::Format::B8G8R8A8_UNORM,
+            color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        }));
+    }
+    let exact = preferred.iter().find(|p| {
+        available
+            .iter()
+            .any(|a| a.format == p.format && a.color_space == p.color_space)
+    });
+    let same_format = || {
+        preferred
+            .iter()
+            .find_map(|p| available.iter().find(|a| a.format == p.format))
+    };
+    exact
+        .or_else(same_format)
+        .or_else(|| available.first())
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(format: vk::Format, color_space: vk::ColorSpaceKHR) -> vk::SurfaceFormatKHR {
+        vk::SurfaceFormatKHR {
+            format,
+            color_space,
+        }
+    }
+
+    #[test]
+    fn present_mode_falls_back_to_fifo() {
+        let available = [vk::PresentModeKHR::FIFO, vk::PresentModeKHR::IMMEDIATE];
+        let preferred = [vk::PresentModeKHR::MAILBOX, vk::PresentModeKHR::IMMEDIATE];
+        assert_eq!(
+            choose_present_mode(&available, &preferred),
+            vk::PresentModeKHR::IMMEDIATE
+        );
+        assert_eq!(
+            choose_present_mode(&available, &[vk::PresentModeKHR::MAILBOX]),
+            vk::PresentModeKHR::FIFO
+        );
+        assert_eq!(choose_present_mode(&[], &[]), vk::PresentModeKHR::FIFO);
+    }
+
+    #[test]
+    fn surface_format_order() {
+        let srgb = vk::ColorSpaceKHR::SRGB_NONLINEAR;
+        let hdr = vk::ColorSpaceKHR::HDR10_ST2084_EXT;
+        let available = [
+            format(vk::Format::R8G8B8A8_UNORM, srgb),
+            format(vk::Format::B8G8R8A8_SRGB, hdr),
+            format(vk::Format::B8G8R8A8_UNORM, srgb),
+        ];
+        // an exact match wins over an earlier preference that only matches the format
+        let preferred = [
+            format(vk::Format::B8G8R8A8_SRGB, srgb),
+            format(vk::Format::B8G8R8A8_UNORM, srgb),
+        ];
+        assert_eq!(
+            choose_surface_format(&available, &preferred),
+            Some(format(vk::Format::B8G8R8A8_UNORM, srgb))
+        );
+        // then the format in another color space
+        assert_eq!(
+            choose_surface_format(&available, &preferred[..1]),
+            Some(format(vk::Format::B8G8R8A8_SRGB, hdr))
+        );
+        // then whatever comes first
+        assert_eq!(
+            choose_surface_format(&available, &[format(vk::Format::R16G16B16A16_SFLOAT, hdr)]),
+            Some(available[0])
+        );
+        assert_eq!(choose_surface_format(&[], &preferred), None);
+    }
+
+    #[test]
+    fn undefined_surface_format_takes_the_preference() {
+        let srgb = vk::ColorSpaceKHR::SRGB_NONLINEAR;
+        let any = [format(vk::Format::UNDEFINED, srgb)];
+        let preferred = [format(vk::Format::B8G8R8A8_SRGB, srgb)];
+        assert_eq!(choose_surface_format(&any, &preferred), Some(preferred[0]));
+        assert_eq!(
+            choose_surface_format(&any, &[]),
+            Some(format(vk::Format::B8G8R8A8_UNORM, srgb))
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use crate::buffer::Buffer;
+use crate::error::{Context, FaeError};
+use crate::instance_device_queues::{QueueFamilies, Queues};
+use crate::pools_and_command_buffers::Pools;
+use crate::resources::FaeResources;
+use ash::{version::DeviceV1_0, vk};
+use std::rc::Rc;
+
+// copies data into GpuOnly buffers through a staging buffer on the transfer queue. If the
+// transfer queue belongs to another family than the graphics queue, ownership of the buffer
+// is released by the transfer queue and acquired by the graphics queue, the two submissions
+// are chained with a semaphore. Uploads block until the buffer can be used for drawing.
+pub struct FaeUploader {
+    logical_device: ash::Device,
+    resources: Rc<FaeResources>,
+    graphics_queue: vk::Queue,
+    transfer_queue: vk::Queue,
+    graphics_q_index: u32,
+    transfer_q_index: u32,
+    command_pool_graphics: vk::CommandPool,
+    command_pool_transfer: vk::CommandPool,
+}
+
+impl FaeUploader {
+    pub fn init(
+        logical_device: &ash::Device,
+        resources: &Rc<FaeResources>,
+        queue_families: &QueueFamilies,
+        queues: &Queues,
+        pools: &Pools,
+    ) -> FaeUploader {
+        FaeUploader {
+            logical_device: logical_device.clone(),
+            resources: resources.clone(),
+            graphics_queue: queues.graphics_queue,
+            transfer_queue: queues.transfer_queue,
+            graphics_q_index: queue_families.graphics_q_index,
+            transfer_q_index: queue_families.transfer_q_index,
+            command_pool_graphics: pools.command_pool_graphics,
+            command_pool_transfer: pools.command_pool_transfer,
+        }
+    }
+
+    // uploads data into a new buffer with the given name that replaces the old one. The old
+    // buffer may still be read by frames in flight, dropping it only queues it for destruction.
+    pub fn upload<T: Sized>(
+        &self,
+        buffer: &mut Option<Buffer>,
+        name: &str,
+        usage: vk::BufferUsageFlags,
+        data: &[T],
+    ) -> Result<(), FaeError> {
+        let size_in_bytes = (data.len() * std::mem::size_of::<T>()) as u64;
+        if size_in_bytes == 0 {
+            return Ok(());
+        }
+        let destination = Buffer::new(
+            &self.resources,
+            name,
+            size_in_bytes,
+            usage | vk::BufferUsageFlags::TRANSFER_DST,
+            vk_mem::MemoryUsage::GpuOnly,
+        )?;
+        let mut staging = Buffer::new(
+            &self.resources,
+            &format!("{} staging", name),
+            size_in_bytes,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk_mem::MemoryUsage::CpuToGpu,
+        )?;
+        staging.write_at(0, data);
+        self.copy(staging.buffer, destination.buffer, size_in_bytes, usage)?;
+        *buffer = Some(destination);
+        Ok(())
+    }
+
+    fn copy(
+        &self,
+        source: vk::Buffer,
+        destination: vk::Buffer,
+        size_in_bytes: u64,
+        usage: vk::BufferUsageFlags,
+    ) -> Result<(), FaeError> {
+        let ownership_transfer = self.transfer_q_index != self.graphics_q_index;
+        let device = &self.logical_device;
+        let transfer_command_buffer = self
+            .begin_one_time(self.command_pool_transfer)
+            .context("beginning the upload commands")?;
+        let graphics_command_buffer = if ownership_transfer {
+            Some(
+                self.begin_one_time(self.command_pool_graphics)
+                    .context("beginning the upload commands")?,
+            )
+        } else {
+            None
+        };
+        let dst_access_mask = if usage.contains(vk::BufferUsageFlags::INDEX_BUFFER) {
+            vk::AccessFlags::INDEX_READ
+        } else {
+            vk::AccessFlags::VERTEX_ATTRIBUTE_READ
+        };
+        let (src_queue_family, dst_queue_family) = if ownership_transfer {
+            (self.transfer_q_index, self.graphics_q_index)
+        } else {
+            (vk::QUEUE_FAMILY_IGNORED, vk::QUEUE_FAMILY_IGNORED)
+        };
+        let barrier = vk::BufferMemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(dst_access_mask)
+            .src_queue_family_index(src_queue_family)
+            .dst_queue_family_index(dst_queue_family)
+            .buffer(destination)
+            .offset(0)
+            .size(vk::WHOLE_SIZE)
+            .build();
+        unsafe {
+            device.cmd_copy_buffer(
+                transfer_command_buffer,
+                source,
+                destination,
+                &[vk::BufferCopy {
+                    src_offset: 0,
+                    dst_offset: 0,
+                    size: size_in_bytes,
+                }],
+            );
+            // release on the transfer queue, or a plain barrier if both queues are the same
+            device.cmd_pipeline_barrier(
+                transfer_command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                if ownership_transfer {
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE
+                } else {
+                    vk::PipelineStageFlags::VERTEX_INPUT
+                },
+                vk::DependencyFlags::empty(),
+                &[],
+                &[vk::BufferMemoryBarrier {
+                    dst_access_mask: if ownership_transfer {
+                        vk::AccessFlags::empty()
+                    } else {
+                        dst_access_mask
+                    },
+                    ..barrier
+                }],
+                &[],
+            );
+            device
+                .end_command_buffer(transfer_command_buffer)
+                .context("recording the upload commands")?;
+            // acquire on the graphics queue
+            if let Some(graphics_command_buffer) = graphics_command_buffer {
+                device.cmd_pipeline_barrier(
+                    graphics_command_buffer,
+                    vk::PipelineStageFlags::TOP_OF_PIPE,
+                    vk::PipelineStageFlags::VERTEX_INPUT,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[vk::BufferMemoryBarrier {
+                        src_access_mask: vk::AccessFlags::empty(),
+                        ..barrier
+                    }],
+                    &[],
+                );
+                device
+                    .end_command_buffer(graphics_command_buffer)
+                    .context("recording the upload commands")?;
+            }
+        }
+
+        let semaphore_create_info = vk::SemaphoreCreateInfo::builder();
+        let semaphore = unsafe { device.create_semaphore(&semaphore_create_info, None) }
+            .context("creating the upload semaphore")?;
+        let fence = unsafe { device.create_fence(&vk::FenceCreateInfo::builder(), None) }
+            .context("creating the upload fence")?;
+        let transfer_command_buffers = [transfer_command_buffer];
+        let semaphores = [semaphore];
+        let mut transfer_submit =
+            vk::SubmitInfo::builder().command_buffers(&transfer_command_buffers);
+        if ownership_transfer {
+            transfer_submit = transfer_submit.signal_semaphores(&semaphores);
+        }
+        let submitted = unsafe {
+            match graphics_command_buffer {
+                Some(graphics_command_buffer) => device
+                    .queue_submit(
+                        self.transfer_queue,
+                        &[transfer_submit.build()],
+                        vk::Fence::null(),
+                    )
+                    .and_then(|_| {
+                        let graphics_command_buffers = [graphics_command_buffer];
+                        let waiting_stages = [vk::PipelineStageFlags::VERTEX_INPUT];
+                        let graphics_submit = vk::SubmitInfo::builder()
+                            .wait_semaphores(&semaphores)
+                            .wait_dst_stage_mask(&waiting_stages)
+                            .command_buffers(&graphics_command_buffers);
+                        device.queue_submit(self.graphics_queue, &[graphics_submit.build()], fence)
+                    }),
+                None => device.queue_submit(self.transfer_queue, &[transfer_submit.build()], fence),
+            }
+        };
+        let finished = submitted
+            .and_then(|_| unsafe { device.wait_for_fences(&[fence], true, std::u64::MAX) });
+        unsafe {
+            device.destroy_fence(fence, None);
+            device.destroy_semaphore(semaphore, None);
+            device.free_command_buffers(self.command_pool_transfer, &transfer_command_buffers);
+            if let Some(graphics_command_buffer) = graphics_command_buffer {
+                device.free_command_buffers(self.command_pool_graphics, &[graphics_command_buffer]);
+            }
+        }
+        finished.context("submitting an upload")
+    }
+
+    fn begin_one_time(
+        &self,
+        command_pool: vk::CommandPool,
+    ) -> Result<vk::CommandBuffer, vk::Result> {
+        let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
+            .command_pool(command_pool)
+            .command_buffer_count(1);
+        let command_buffer = unsafe {
+            self.logical_device
+                .allocate_command_buffers(&command_buffer_allocate_info)
+        }?[0];
+        let command_buffer_begin_info = vk::CommandBufferBeginInfo::builder()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        unsafe {
+            self.logical_device
+                .begin_command_buffer(command_buffer, &command_buffer_begin_info)
+        }?;
+        Ok(command_buffer)
+    }
+}
@@ -0,0 +1,140 @@
+use ash::vk;
+
+// a type that can be a field of a vertex or instance, with the format and offset inside the
+// field of every shader location it takes. Matrices take one location per column.
+pub trait VertexFormat {
+    const LOCATIONS: &'static [(u32, vk::Format)];
+}
+
+impl VertexFormat for f32 {
+    const LOCATIONS: &'static [(u32, vk::Format)] = &[(0, vk::Format::R32_SFLOAT)];
+}
+
+impl VertexFormat for [f32; 2] {
+    const LOCATIONS: &'static [(u32, vk::Format)] = &[(0, vk::Format::R32G32_SFLOAT)];
+}
+
+impl VertexFormat for [f32; 3] {
+    const LOCATIONS: &'static [(u32, vk::Format)] = &[(0, vk::Format::R32G32B32_SFLOAT)];
+}
+
+impl VertexFormat for [f32; 4] {
+    const LOCATIONS: &'static [(u32, vk::Format)] = &[(0, vk::Format::R32G32B32A32_SFLOAT)];
+}
+
+impl VertexFormat for [[f32; 4]; 4] {
+    const LOCATIONS: &'static [(u32, vk::Format)] = &[
+        (0, vk::Format::R32G32B32A32_SFLOAT),
+        (16, vk::Format::R32G32B32A32_SFLOAT),
+        (32, vk::Format::R32G32B32A32_SFLOAT),
+        (48, vk::Format::R32G32B32A32_SFLOAT),
+    ];
+}
+
+impl VertexFormat for u32 {
+    const LOCATIONS: &'static [(u32, vk::Format)] = &[(0, vk::Format::R32_UINT)];
+}
+
+impl VertexFormat for i32 {
+    const LOCATIONS: &'static [(u32, vk::Format)] = &[(0, vk::Format::R32_SINT)];
+}
+
+impl VertexFormat for [u8; 4] {
+    // colors packed into 4 bytes, read as vec4 in 0..1
+    const LOCATIONS: &'static [(u32, vk::Format)] = &[(0, vk::Format::R8G8B8A8_UNORM)];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VertexAttribute {
+    // bytes from the start of the vertex or instance
+    pub offset: u32,
+    pub format: vk::Format,
+}
+
+impl VertexAttribute {
+    // the attributes of a field at the given offset, the pointer is only there to name its type
+    pub fn of_field<T: VertexFormat>(offset: u32, _field: *const T) -> Vec<VertexAttribute> {
+        T::LOCATIONS
+            .iter()
+            .map(|&(location_offset, format)| VertexAttribute {
+                offset: offset + location_offset,
+                format,
+            })
+            .collect()
+    }
+}
+
+// how a vertex or instance type is read by the vertex shader, one attribute per location in
+// shader order. Use impl_vertex_layout! instead of implementing this by hand.
+pub trait VertexLayout: Sized {
+    fn attributes() -> Vec<VertexAttribute>;
+
+    fn stride() -> u32 {
+        std::mem::size_of::<Self>() as u32
+    }
+}
+
+// implements VertexLayout for a #[repr(C)] struct, the listed fields get consecutive shader
+// locations in the order given: impl_vertex_layout!(VertexData { position, normal });
+#[macro_export]
+macro_rules! impl_vertex_layout {
+    ($type:ty { $($field:ident),* $(,)? }) => {
+        impl $crate::VertexLayout for $type {
+            fn attributes() -> Vec<$crate::VertexAttribute> {
+                let element = std::mem::MaybeUninit::<$type>::uninit();
+                let base = element.as_ptr();
+                let mut attributes = Vec::new();
+                $(
+                    // only the address is taken, the uninitialised field is never read
+                    let field = unsafe { std::ptr::addr_of!((*base).$field) };
+                    let offset = (field as usize - base as usize) as u32;
+                    attributes.extend($crate::VertexAttribute::of_field(offset, field));
+                )*
+                attributes
+            }
+        }
+    };
+}
+
+// vertex input of a pipeline: binding 0 advances per vertex, binding 1 per instance and the
+// locations are numbered through both, starting with the vertex attributes
+#[derive(Debug, Clone)]
+pub struct VertexInput {
+    pub bindings: Vec<vk::VertexInputBindingDescription>,
+    pub attributes: Vec<vk::VertexInputAttributeDescription>,
+}
+
+impl VertexInput {
+    pub fn of<V: VertexLayout, I: VertexLayout>() -> VertexInput {
+        let bindings = vec![
+            vk::VertexInputBindingDescription {
+                binding: 0,
+                stride: V::stride(),
+                input_rate: vk::VertexInputRate::VERTEX,
+            },
+            vk::VertexInputBindingDescription {
+                binding: 1,
+                stride: I::stride(),
+                input_rate: vk::VertexInputRate::INSTANCE,
+            },
+        ];
+        let per_vertex = V::attributes().into_iter().map(|a| (0, a));
+        let per_instance = I::attributes().into_iter().map(|a| (1, a));
+        let attributes = per_vertex
+            .chain(per_instance)
+            .enumerate()
+            .map(
+                |(location, (binding, attribute))| vk::VertexInputAttributeDescription {
+                    binding,
+                    location: location as u32,
+                    offset: attribute.offset,
+                    format: attribute.format,
+                },
+            )
+            .collect();
+        VertexInput {
+            bindings,
+            attributes,
+        }
+    }
+}